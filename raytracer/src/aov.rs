@@ -0,0 +1,463 @@
+use crate::clamp;
+use crate::error::{RenderError, Result};
+use crate::film::{to_rgb_image, Film};
+use crate::integrator::{DIRECT, EMISSION, FIRST_LIGHT, INDIRECT};
+use crate::vec3::Vec3;
+#[cfg(feature = "exr")]
+use exr::prelude::*;
+use image::{ImageBuffer, RgbImage};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+//Arbitrary output variables, the passes written next to the beauty image for
+//compositing. Surface passes come from the first hit of the camera rays and
+//need RenderSettings::features, the lighting ones need
+//RenderSettings::split_lighting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    Depth,
+    Position,
+    Normal,
+    Albedo,
+    Uv,
+    MaterialId,
+    ObjectId,
+    Emission,
+    Direct,
+    Indirect,
+    //one pass per light, then other emitters and the background
+    Lights,
+}
+
+pub const ALL_AOVS: [Aov; 11] = [
+    Aov::Depth,
+    Aov::Position,
+    Aov::Normal,
+    Aov::Albedo,
+    Aov::Uv,
+    Aov::MaterialId,
+    Aov::ObjectId,
+    Aov::Emission,
+    Aov::Direct,
+    Aov::Indirect,
+    Aov::Lights,
+];
+
+impl Aov {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+            Aov::Emission => "emission",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Lights => "lights",
+        }
+    }
+
+    pub fn needs_features(&self) -> bool {
+        !self.needs_lighting()
+    }
+
+    pub fn needs_lighting(&self) -> bool {
+        matches!(
+            self,
+            Aov::Emission | Aov::Direct | Aov::Indirect | Aov::Lights
+        )
+    }
+
+    //"all" or comma separated names, e.g. "depth,normal,direct"
+    pub fn parse_list(s: &str) -> std::result::Result<Vec<Aov>, String> {
+        if s == "all" {
+            return Ok(ALL_AOVS.to_vec());
+        }
+        let mut aovs = Vec::new();
+        for name in s.split(',') {
+            let aov = name.parse()?;
+            if !aovs.contains(&aov) {
+                aovs.push(aov);
+            }
+        }
+        Ok(aovs)
+    }
+
+    //the passes of this AOV in the film, empty for lighting AOVs of films
+    //rendered without split_lighting
+    pub fn passes(&self, film: &Film) -> Vec<Pass> {
+        let features = || {
+            (0..film.height)
+                .flat_map(|y| (0..film.width).map(move |x| film.features(x, y)))
+                .collect::<Vec<_>>()
+        };
+        let lighting = |k: usize| {
+            (0..film.height)
+                .flat_map(|y| (0..film.width).map(move |x| film.lighting(x, y, k)))
+                .collect()
+        };
+        let name = self.name().to_string();
+        let data = match self {
+            Aov::Depth => PassData::Depth(features().iter().map(|f| f.depth).collect()),
+            Aov::Position => PassData::Position(features().iter().map(|f| f.position).collect()),
+            Aov::Normal => PassData::Normal(
+                features()
+                    .iter()
+                    .map(|f| {
+                        if f.normal.near_zero() {
+                            Vec3::zero()
+                        } else {
+                            f.normal.unit()
+                        }
+                    })
+                    .collect(),
+            ),
+            Aov::Albedo => PassData::Color(features().iter().map(|f| f.albedo).collect()),
+            Aov::Uv => PassData::Uv(
+                features()
+                    .iter()
+                    .map(|f| Vec3::new(f.u, f.v, 0.0))
+                    .collect(),
+            ),
+            Aov::MaterialId => PassData::Id(dense_ids(features().iter().map(|f| f.material_id))),
+            Aov::ObjectId => PassData::Id(features().iter().map(|f| f.object_id).collect()),
+            _ if film.lighting_passes() == 0 => return Vec::new(),
+            Aov::Emission => PassData::Color(lighting(EMISSION)),
+            Aov::Direct => PassData::Color(lighting(DIRECT)),
+            Aov::Indirect => PassData::Color(lighting(INDIRECT)),
+            Aov::Lights => {
+                let n_lights = film.lighting_passes() - FIRST_LIGHT - 2;
+                return (0..n_lights + 2)
+                    .map(|k| {
+                        let name = match k {
+                            _ if k < n_lights => format!("light_{}", k),
+                            _ if k == n_lights => "light_other".to_string(),
+                            _ => "light_background".to_string(),
+                        };
+                        Pass {
+                            name,
+                            data: PassData::Color(lighting(FIRST_LIGHT + k)),
+                        }
+                    })
+                    .collect();
+            }
+        };
+        vec![Pass { name, data }]
+    }
+}
+
+//Numbers the IDs 1, 2, ... in the order they first show up in the image, 0
+//stays the misses'. Material IDs change from run to run, the numbers do not.
+fn dense_ids(ids: impl Iterator<Item = u32>) -> Vec<u32> {
+    let mut numbers = HashMap::new();
+    numbers.insert(0, 0);
+    ids.map(|id| {
+        let next = numbers.len() as u32;
+        *numbers.entry(id).or_insert(next)
+    })
+    .collect()
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ALL_AOVS
+            .iter()
+            .find(|aov| aov.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown AOV {}", s))
+    }
+}
+
+//one output image, rows of pixels
+pub struct Pass {
+    pub name: String,
+    pub data: PassData,
+}
+
+pub enum PassData {
+    //linear radiance or reflectance
+    Color(Vec<Vec3>),
+    //world space points
+    Position(Vec<Vec3>),
+    //unit shading normals, zero for misses
+    Normal(Vec<Vec3>),
+    //texture coordinates in x and y
+    Uv(Vec<Vec3>),
+    //distance along the camera ray, zero for misses
+    Depth(Vec<f64>),
+    Id(Vec<u32>),
+}
+
+impl Pass {
+    //8 bit preview: radiance sRGB encoded, positions normalised to their
+    //bounding box, normals mapped from [-1, 1], depth inverted so near is bright
+    //and ids hashed to colours
+    pub fn to_rgb_image(&self, width: u32, height: u32) -> RgbImage {
+        let pixels: Vec<Vec3> = match &self.data {
+            PassData::Color(c) => return to_rgb_image(width, height, c),
+            PassData::Position(p) => {
+                let mut min = Vec3::ones() * f64::INFINITY;
+                let mut max = -min;
+                for p in p.iter() {
+                    min = Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+                    max = Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+                }
+                let extent = max - min;
+                let scale = |x: f64, e: f64| if e > 0.0 { x / e } else { 0.0 };
+                p.iter()
+                    .map(|p| {
+                        let d = *p - min;
+                        Vec3::new(
+                            scale(d.x, extent.x),
+                            scale(d.y, extent.y),
+                            scale(d.z, extent.z),
+                        )
+                    })
+                    .collect()
+            }
+            PassData::Normal(n) => n
+                .iter()
+                .map(|n| {
+                    if n.near_zero() {
+                        Vec3::zero()
+                    } else {
+                        *n * 0.5 + Vec3::ones() * 0.5
+                    }
+                })
+                .collect(),
+            PassData::Uv(uv) => uv.clone(),
+            PassData::Depth(z) => {
+                let max = z.iter().copied().fold(0.0, f64::max);
+                z.iter()
+                    .map(|z| {
+                        if *z > 0.0 {
+                            Vec3::ones() * (1.0 - 0.9 * z / max)
+                        } else {
+                            Vec3::zero()
+                        }
+                    })
+                    .collect()
+            }
+            PassData::Id(ids) => ids.iter().map(|id| id_color(*id)).collect(),
+        };
+        let mut img: RgbImage = ImageBuffer::new(width, height);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let c = pixels[(y * width + x) as usize];
+            let byte = |c: f64| (256.0 * clamp(c, 0.0, 0.999)) as u8;
+            *pixel = image::Rgb([byte(c.x), byte(c.y), byte(c.z)]);
+        }
+        img
+    }
+
+    #[cfg(feature = "exr")]
+    fn channels(&self) -> Channels {
+        match &self.data {
+            PassData::Color(c) | PassData::Position(c) => vec3_channels(c, &["R", "G", "B"]),
+            PassData::Normal(n) => vec3_channels(n, &["X", "Y", "Z"]),
+            PassData::Uv(uv) => vec3_channels(uv, &["U", "V"]),
+            PassData::Depth(z) => {
+                let z = z.iter().map(|z| *z as f32).collect();
+                let mut channels = SmallVec::new();
+                channels.push(AnyChannel::new("Z", FlatSamples::F32(z)));
+                channels
+            }
+            PassData::Id(ids) => {
+                let mut channels = SmallVec::new();
+                channels.push(AnyChannel::new("ID", FlatSamples::U32(ids.clone())));
+                channels
+            }
+        }
+    }
+}
+
+#[cfg(feature = "exr")]
+type Channels = SmallVec<[AnyChannel<FlatSamples>; 4]>;
+
+//float channels of the first names.len() components of the vectors
+#[cfg(feature = "exr")]
+fn vec3_channels(values: &[Vec3], names: &[&str]) -> Channels {
+    names
+        .iter()
+        .enumerate()
+        .map(|(k, name)| {
+            let samples = values.iter().map(|v| [v.x, v.y, v.z][k] as f32).collect();
+            AnyChannel::new(*name, FlatSamples::F32(samples))
+        })
+        .collect()
+}
+
+//well spread colour of an id, black stays black for misses
+fn id_color(id: u32) -> Vec3 {
+    if id == 0 {
+        return Vec3::zero();
+    }
+    let mut h = id;
+    h = (h ^ (h >> 16)).wrapping_mul(0x045d_9f3b);
+    h = (h ^ (h >> 16)).wrapping_mul(0x045d_9f3b);
+    h ^= h >> 16;
+    let byte = |shift: u32| 0.2 + 0.8 * ((h >> shift) & 0xff) as f64 / 255.0;
+    Vec3::new(byte(0), byte(8), byte(16))
+}
+
+//Writes the beauty image and the passes as layers of one multi-part EXR, in
+//linear float so compositing can redo any tone mapping.
+#[cfg(feature = "exr")]
+pub fn write_exr(
+    path: &str,
+    width: u32,
+    height: u32,
+    beauty: &[Vec3],
+    passes: &[Pass],
+) -> Result<()> {
+    let size = (width as usize, height as usize);
+    let layer = |name: &str, channels| {
+        Layer::new(
+            size,
+            LayerAttributes::named(name),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        )
+    };
+    let mut layers = vec![layer("beauty", vec3_channels(beauty, &["R", "G", "B"]))];
+    for pass in passes {
+        layers.push(layer(&pass.name, pass.channels()));
+    }
+    let image = Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+        layers,
+    );
+    image
+        .write()
+        .to_file(path)
+        .map_err(|e| RenderError::exr(path, e))
+}
+
+#[cfg(not(feature = "exr"))]
+pub fn write_exr(
+    path: &str,
+    _width: u32,
+    _height: u32,
+    _beauty: &[Vec3],
+    _passes: &[Pass],
+) -> Result<()> {
+    Err(RenderError::exr_disabled(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::hittable_list::HittableList;
+    use crate::integrator::{first_hit_features, lighting_passes, ray_color};
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::ray::Ray;
+    use crate::rectangle::XzRect;
+    use crate::seed_random;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lighting_split_sums_to_beauty() {
+        let grey = || Lambertian::new(SolidColor::new_with_vec(Vec3::new(0.5, 0.5, 0.5)));
+        let light = Arc::new(XzRect::new(
+            -1.0,
+            1.0,
+            -1.0,
+            1.0,
+            3.0,
+            DiffuseLight::new(SolidColor::new_with_vec(Vec3::new(4.0, 4.0, 4.0))),
+        ));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Vec3::zero(), 1.0, grey())));
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, -101.0, 0.0),
+            100.0,
+            grey(),
+        )));
+        world.add(light.clone());
+        let mut lights = HittableList::new();
+        lights.add(light);
+        let background = Vec3::new(0.1, 0.1, 0.2);
+        let mut direct = Vec3::zero();
+        for i in 0..64 {
+            let dir = Vec3::new(i as f64 / 32.0 - 1.0, -0.3, -1.0);
+            let r = Ray::new(Vec3::new(0.0, 0.5, 4.0), dir, 0.0);
+            seed_random(i);
+            let beauty = ray_color(&r, &background, &world, &lights, 6, &mut []);
+            seed_random(i);
+            let mut passes = vec![Vec3::zero(); lighting_passes(&lights)];
+            let color = ray_color(&r, &background, &world, &lights, 6, &mut passes);
+            assert_eq!(color, beauty);
+            direct += passes[DIRECT];
+            let bounces = passes[EMISSION] + passes[DIRECT] + passes[INDIRECT];
+            let sources = passes[FIRST_LIGHT..]
+                .iter()
+                .fold(Vec3::zero(), |a, b| a + *b);
+            for total in [bounces, sources].iter() {
+                assert!((*total - beauty).length() < 1e-9 * (1.0 + beauty.length()));
+            }
+        }
+        assert!(direct.x > 0.0);
+    }
+
+    #[test]
+    fn test_material_id_per_instance() {
+        let grey = || Lambertian::new(SolidColor::new_with_vec(Vec3::new(0.5, 0.5, 0.5)));
+        let shared = Arc::new(grey());
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(-3.0, 0.0, 0.0),
+            1.0,
+            grey(),
+        )));
+        world.add(Arc::new(Sphere::new(Vec3::zero(), 1.0, grey())));
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(3.0, 0.0, 0.0),
+            1.0,
+            shared.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(Vec3::new(6.0, 0.0, 0.0), 1.0, shared)));
+        let mut film = Film::new(5, 1);
+        for x in 0..5 {
+            let r = Ray::new(
+                Vec3::new(x as f64 * 3.0 - 3.0, 0.0, 5.0),
+                Vec3::new(0.0, 0.0, -1.0),
+                0.0,
+            );
+            let features = first_hit_features(&r, &Vec3::zero(), &world);
+            film.add_sample(
+                x as f64 + 0.5,
+                0.5,
+                Vec3::zero(),
+                &[],
+                &features,
+                &Filter::pixel_box(),
+            );
+        }
+        let ids = match &Aov::MaterialId.passes(&film)[0].data {
+            PassData::Id(ids) => ids.clone(),
+            _ => unreachable!(),
+        };
+        //two Lambertians of their own, one shared by two spheres and a miss
+        assert_eq!(ids, vec![1, 2, 3, 3, 0]);
+    }
+
+    #[test]
+    fn test_parse_aov_list() {
+        assert_eq!(
+            Aov::parse_list("depth,normal,depth").unwrap(),
+            vec![Aov::Depth, Aov::Normal]
+        );
+        assert_eq!(Aov::parse_list("all").unwrap().len(), ALL_AOVS.len());
+        assert!(Aov::parse_list("depth,beauty").is_err());
+        for aov in ALL_AOVS.iter() {
+            assert_eq!(aov.name().parse::<Aov>().unwrap(), *aov);
+        }
+    }
+}
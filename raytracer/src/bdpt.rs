@@ -0,0 +1,709 @@
+use crate::camera::Camera;
+use crate::emitter::Emitters;
+use crate::film::Splat;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::integrator::light_index;
+use crate::pdf::PDF;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::{random_0_1, INF, PI};
+use std::sync::Arc;
+
+//bounces before russian roulette may end a subpath
+const ROULETTE_DEPTH: usize = 3;
+//shadow rays stop this fraction short of their target
+const SHADOW_EPS: f64 = 1e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Camera,
+    //point on entry k of the lights list
+    Light(usize),
+    //the background, p lies outside the scene in the direction it is seen
+    Environment,
+    Surface,
+}
+
+#[derive(Clone)]
+struct Vertex<'a> {
+    kind: Kind,
+    p: Vec3,
+    //normal to take cosines with, zero for the camera, media and the background
+    n: Vec3,
+    //the hit and the ray that arrived at it, surfaces only
+    rec: Option<HitRecord<'a>>,
+    r_in: Ray,
+    //attenuation and direction density of the scatter, pdf is None for vertices
+    //that cannot be connected to: cameras and lights, emitters that do not
+    //scatter and specular surfaces
+    attenuation: Vec3,
+    pdf: Option<Arc<dyn PDF>>,
+    //light emitted back along r_in for surfaces, along the path for lights
+    le: Vec3,
+    beta: Vec3,
+    delta: bool,
+    //area densities of sampling this vertex from the previous one and from the
+    //next one, solid angle densities for the background
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl<'a> Vertex<'a> {
+    fn endpoint(kind: Kind, p: Vec3, n: Vec3, le: Vec3, beta: Vec3) -> Self {
+        Self {
+            kind,
+            p,
+            n,
+            rec: None,
+            r_in: Ray::new(p, Vec3::zero(), 0.0),
+            attenuation: Vec3::zero(),
+            pdf: None,
+            le,
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn on_surface(&self) -> bool {
+        self.n != Vec3::zero()
+    }
+
+    fn is_light(&self) -> bool {
+        self.kind != Kind::Camera && self.le != Vec3::zero()
+    }
+
+    fn connectible(&self) -> bool {
+        match self.kind {
+            Kind::Surface => self.pdf.is_some(),
+            _ => true,
+        }
+    }
+
+    //area density at next of the direction density pdf at this vertex
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        if next.kind == Kind::Environment {
+            return pdf;
+        }
+        let w = next.p - self.p;
+        let dist2 = w.squared_length();
+        if dist2 == 0.0 {
+            return 0.0;
+        }
+        if next.on_surface() {
+            pdf * (next.n * w).abs() / (dist2 * dist2.sqrt())
+        } else {
+            pdf / dist2
+        }
+    }
+
+    //attenuation times scattering_value towards next, the BSDF with its cosine
+    fn f(&self, next: &Vertex) -> Vec3 {
+        match (&self.rec, &self.pdf) {
+            (Some(rec), Some(_)) => {
+                let scattered = Ray::new(self.p, next.p - self.p, self.r_in.tm);
+                self.attenuation * rec.mat_ptr.scattering_value(&self.r_in, rec, &scattered)
+            }
+            _ => Vec3::zero(),
+        }
+    }
+
+    //area density at next of continuing the path there, for light that came from prev
+    fn pdf(&self, bdpt: &Bdpt, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let wn = (next.p - self.p).unit();
+        let pdf = match self.kind {
+            Kind::Light(_) | Kind::Environment => return self.pdf_light(bdpt, next),
+            Kind::Camera => bdpt.cam.pdf_dir(&self.p, &wn),
+            Kind::Surface => match (&self.rec, &self.pdf, prev) {
+                (Some(rec), Some(pdf), Some(prev)) => {
+                    if prev.p == self.r_in.orig {
+                        pdf.value(&wn)
+                    } else {
+                        let r = Ray::new(prev.p, self.p - prev.p, self.r_in.tm);
+                        match rec.mat_ptr.scatter(&r, rec) {
+                            Some(s_rec) if !s_rec.is_specular => s_rec.pdf_ptr.value(&wn),
+                            _ => 0.0,
+                        }
+                    }
+                }
+                _ => 0.0,
+            },
+        };
+        self.convert_density(pdf, next)
+    }
+
+    //area density at next of a light path leaving this light vertex towards it
+    fn pdf_light(&self, bdpt: &Bdpt, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let dist2 = w.squared_length();
+        let w = w / dist2.sqrt();
+        let radius = bdpt.emitters.radius;
+        let pdf = match self.kind {
+            Kind::Environment if radius > 0.0 => 1.0 / (PI * radius * radius),
+            Kind::Environment => 0.0,
+            //either side of the light, cosine distributed
+            _ => 0.5 * (self.n * w).abs() / PI / dist2,
+        };
+        if next.on_surface() {
+            pdf * (next.n * w).abs()
+        } else {
+            pdf
+        }
+    }
+
+    //density of a light path starting at this vertex, per area on lights and per
+    //solid angle for the background
+    fn pdf_light_origin(&self, bdpt: &Bdpt) -> f64 {
+        let emitters = &bdpt.emitters;
+        let n_lights = emitters.lights.objects.len();
+        let light = match (self.kind, &self.rec) {
+            (Kind::Environment, _) => return emitters.light_pdf[n_lights] / (4.0 * PI),
+            (Kind::Light(k), _) => k,
+            (Kind::Surface, Some(rec)) => match light_index(emitters.lights, &self.r_in, rec.t) {
+                Some(k) => k,
+                None => return 0.0,
+            },
+            _ => return 0.0,
+        };
+        let area = emitters.lights.objects[light].area();
+        if area > 0.0 {
+            emitters.light_pdf[light] / area
+        } else {
+            0.0
+        }
+    }
+}
+
+//Bidirectional path tracer after Veach's thesis, in the form pbrt-v3 gives it.
+//Every camera sample traces a camera subpath and a light subpath, connects each
+//pair of their prefixes and weights the strategies with the balance heuristic.
+//Connections straight to the camera can land on any pixel and come back as
+//splats. Light subpaths start on the lights list, whose stand-in shapes are
+//looked through for the emitting material, or at the background. RGB only.
+pub struct Bdpt<'a> {
+    world: &'a HittableList,
+    cam: &'a Camera,
+    emitters: Emitters<'a>,
+    max_depth: usize,
+}
+
+impl<'a> Bdpt<'a> {
+    pub fn new(
+        world: &'a HittableList,
+        lights: &'a HittableList,
+        cam: &'a Camera,
+        background: Vec3,
+        max_depth: i32,
+    ) -> Self {
+        Self::with_emitters(
+            world,
+            cam,
+            Emitters::new(world, lights, background),
+            max_depth,
+        )
+    }
+
+    pub fn with_emitters(
+        world: &'a HittableList,
+        cam: &'a Camera,
+        emitters: Emitters<'a>,
+        max_depth: i32,
+    ) -> Self {
+        Self {
+            world,
+            cam,
+            emitters,
+            max_depth: max_depth.max(0) as usize,
+        }
+    }
+
+    //Radiance of the camera ray. Connections of light subpaths to the camera are
+    //pushed to splats in image pixels.
+    pub fn sample(&self, r: &Ray, width: u32, height: u32, splats: &mut Vec<Splat>) -> Vec3 {
+        let mut camera = Vec::with_capacity(self.max_depth + 1);
+        let ones = Vec3::ones();
+        camera.push(Vertex::endpoint(
+            Kind::Camera,
+            r.orig,
+            Vec3::zero(),
+            Vec3::zero(),
+            ones,
+        ));
+        let pdf_dir = self.cam.pdf_dir(&r.orig, &r.dir);
+        self.random_walk(*r, ones, pdf_dir, self.max_depth + 1, true, &mut camera);
+        let mut light = Vec::with_capacity(self.max_depth);
+        self.light_subpath(r.tm, &mut light);
+
+        let mut color = Vec3::zero();
+        for t in 1..=camera.len() {
+            //s = 1 samples its own light vertex, it runs even when the light
+            //subpath left from a side that does not emit
+            for s in 0..=light.len().max(1) {
+                //s + t - 1 hits, the same count ray_color stops at
+                if s + t < 2 || (s == 1 && t == 1) || s + t - 1 > self.max_depth {
+                    continue;
+                }
+                let (l, raster) = self.connect(&light, &camera, s, t);
+                if !(l.x.is_finite() && l.y.is_finite() && l.z.is_finite()) {
+                    continue;
+                }
+                match raster {
+                    Some((u, v)) => splats.push(Splat {
+                        x: ((u * width as f64) as u32).min(width - 1),
+                        y: (((1.0 - v) * height as f64) as u32).min(height - 1),
+                        color: l,
+                    }),
+                    None => color += l,
+                }
+            }
+        }
+        color
+    }
+
+    fn visible(&self, a: Vec3, b: Vec3, tm: f64) -> bool {
+        let d = b - a;
+        let dist = d.length();
+        let r = Ray::new(a, d / dist, tm);
+        self.world
+            .hit(&r, 0.001, dist * (1.0 - SHADOW_EPS))
+            .is_none()
+    }
+
+    fn light_subpath(&self, tm: f64, path: &mut Vec<Vertex<'a>>) {
+        let e = match self.emitters.sample_emission(tm) {
+            Some(e) => e,
+            None => return,
+        };
+        let background = self.emitters.is_background(e.light);
+        let kind = if background {
+            Kind::Environment
+        } else {
+            Kind::Light(e.light)
+        };
+        let mut v = Vertex::endpoint(kind, e.ray.orig, e.n, e.le, e.le);
+        v.pdf_fwd = e.select * if background { e.pdf_dir } else { e.pdf_pos };
+        path.push(v);
+        self.random_walk(e.ray, e.beta(), e.pdf_dir, self.max_depth, false, path);
+        //the background's origin density is per area across the ray and belongs
+        //to the first hit
+        if background && path.len() > 1 {
+            let first = &mut path[1];
+            first.pdf_fwd = if first.on_surface() {
+                e.pdf_pos * (first.n * e.ray.dir).abs()
+            } else {
+                e.pdf_pos
+            };
+        }
+    }
+
+    //Extends the path from its last vertex along ray, which was sampled with
+    //solid angle density pdf_fwd, until it holds max_len vertices, leaves the
+    //scene or is absorbed. Camera paths keep a vertex for the background.
+    fn random_walk(
+        &self,
+        mut ray: Ray,
+        mut beta: Vec3,
+        mut pdf_fwd: f64,
+        max_len: usize,
+        camera: bool,
+        path: &mut Vec<Vertex<'a>>,
+    ) {
+        let mut throughput = Vec3::ones();
+        let mut bounces = 0;
+        while path.len() < max_len {
+            let prev = path.len() - 1;
+            let mut rec = match self.world.hit(&ray, 0.001, INF) {
+                Some(rec) => rec,
+                None => {
+                    if camera && self.emitters.background != Vec3::zero() {
+                        let dir = ray.dir.unit();
+                        let p = path[prev].p + dir * self.emitters.far();
+                        let bg = self.emitters.background;
+                        let mut v = Vertex::endpoint(Kind::Environment, p, Vec3::zero(), bg, beta);
+                        v.pdf_fwd = pdf_fwd;
+                        path.push(v);
+                    }
+                    break;
+                }
+            };
+            if camera {
+                rec.set_footprint(&ray);
+            }
+            let r_in = rec.incident.unwrap_or(ray);
+            let mat = rec.mat_ptr;
+            let mut v = Vertex {
+                kind: Kind::Surface,
+                p: rec.p,
+                n: if mat.is_volumetric() {
+                    Vec3::zero()
+                } else {
+                    rec.normal
+                },
+                rec: Some(rec),
+                r_in,
+                attenuation: Vec3::zero(),
+                pdf: None,
+                le: Vec3::elemul(mat.emitted(&r_in, &rec, rec.u, rec.v, &rec.p), rec.weight),
+                beta,
+                //a walk inside the object got here, its pdfs are not known
+                delta: rec.incident.is_some(),
+                pdf_fwd: 0.0,
+                pdf_rev: 0.0,
+            };
+            v.pdf_fwd = path[prev].convert_density(pdf_fwd, &v);
+            let s_rec = match mat.scatter(&r_in, &rec) {
+                Some(s_rec) => s_rec,
+                None => {
+                    path.push(v);
+                    break;
+                }
+            };
+            v.attenuation = Vec3::elemul(s_rec.attenuation, rec.weight);
+            let mut pdf_rev = 0.0;
+            let mut next = if s_rec.is_specular {
+                v.delta = true;
+                //light paths carry importance, which refraction scales
+                let eta = if camera { 1.0 } else { s_rec.eta * s_rec.eta };
+                beta = Vec3::elemul(beta, v.attenuation) * eta;
+                throughput = Vec3::elemul(throughput, v.attenuation) * eta;
+                pdf_fwd = 0.0;
+                s_rec.specular_ray
+            } else {
+                let dir = s_rec.pdf_ptr.generate();
+                pdf_fwd = s_rec.pdf_ptr.value(&dir);
+                let scattered = Ray::new(rec.p, dir, ray.tm);
+                let f = v.attenuation * mat.scattering_value(&r_in, &rec, &scattered);
+                if pdf_fwd.is_nan() || pdf_fwd <= 0.0 || f == Vec3::zero() {
+                    path.push(v);
+                    break;
+                }
+                beta = Vec3::elemul(beta, f) / pdf_fwd;
+                throughput = Vec3::elemul(throughput, f) / pdf_fwd;
+                if v.delta {
+                    pdf_fwd = 0.0;
+                } else {
+                    //the density of scattering back along the incoming ray
+                    let back = Ray::new(rec.p + dir, -dir, ray.tm);
+                    if let Some(s_back) = mat.scatter(&back, &rec) {
+                        if !s_back.is_specular {
+                            pdf_rev = s_back.pdf_ptr.value(&-ray.dir);
+                        }
+                    }
+                    v.pdf = Some(s_rec.pdf_ptr);
+                }
+                scattered
+            };
+            if camera {
+                next.inherit_cone(&ray, rec.t);
+            }
+            path.push(v);
+            let rev = path[prev + 1].convert_density(pdf_rev, &path[prev]);
+            path[prev].pdf_rev = rev;
+
+            bounces += 1;
+            if bounces >= ROULETTE_DEPTH {
+                let q = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+                if random_0_1() >= q {
+                    break;
+                }
+                beta /= q;
+                throughput /= q;
+            }
+            ray = next;
+        }
+    }
+
+    //The strategy with s light and t camera vertices, and the image position
+    //for strategies that connect to the camera.
+    fn connect(
+        &self,
+        light: &[Vertex<'a>],
+        camera: &[Vertex<'a>],
+        s: usize,
+        t: usize,
+    ) -> (Vec3, Option<(f64, f64)>) {
+        let none = (Vec3::zero(), None);
+        let tm = camera[0].r_in.tm;
+        if t > 1 && s != 0 && camera[t - 1].kind == Kind::Environment {
+            return none;
+        }
+        let mut raster = None;
+        let mut sampled = None;
+        let l = if s == 0 {
+            let pt = &camera[t - 1];
+            if !pt.is_light() {
+                return none;
+            }
+            Vec3::elemul(pt.le, pt.beta)
+        } else if t == 1 {
+            let qs = &light[s - 1];
+            if !qs.connectible() {
+                return none;
+            }
+            let lens = self.cam.sample_lens();
+            let dir = qs.p - lens;
+            let st = match self.cam.raster(&lens, &dir) {
+                Some(st) => st,
+                None => return none,
+            };
+            let cosine = dir.unit() * self.cam.view_direction();
+            let pdf = dir.squared_length() / (cosine * self.cam.lens_area());
+            let we = self.cam.importance(&dir) / pdf;
+            let v = Vertex::endpoint(
+                Kind::Camera,
+                lens,
+                Vec3::zero(),
+                Vec3::zero(),
+                Vec3::ones() * we,
+            );
+            let l = Vec3::elemul(Vec3::elemul(qs.beta, qs.f(&v)), v.beta);
+            if l == Vec3::zero() || !self.visible(qs.p, lens, tm) {
+                return none;
+            }
+            raster = Some(st);
+            sampled = Some(v);
+            l
+        } else if s == 1 {
+            let pt = &camera[t - 1];
+            if !pt.connectible() {
+                return none;
+            }
+            let v = match self.sample_light_from(pt, tm) {
+                Some(v) => v,
+                None => return none,
+            };
+            let l = Vec3::elemul(Vec3::elemul(pt.beta, pt.f(&v)), v.beta);
+            sampled = Some(v);
+            l
+        } else {
+            let (qs, pt) = (&light[s - 1], &camera[t - 1]);
+            if !qs.connectible() || !pt.connectible() {
+                return none;
+            }
+            let l = Vec3::elemul(
+                Vec3::elemul(qs.beta, qs.f(pt)),
+                Vec3::elemul(pt.f(qs), pt.beta),
+            );
+            if l == Vec3::zero() || !self.visible(qs.p, pt.p, tm) {
+                return none;
+            }
+            //the cosines are part of f
+            l / (qs.p - pt.p).squared_length()
+        };
+        if l == Vec3::zero() {
+            return none;
+        }
+        (
+            l * self.mis_weight(light, camera, sampled.as_ref(), s, t),
+            raster,
+        )
+    }
+
+    //a light vertex seen from pt, for the strategy with one light vertex
+    fn sample_light_from(&self, pt: &Vertex<'a>, tm: f64) -> Option<Vertex<'a>> {
+        let li = self.emitters.sample_incident(pt.p, tm)?;
+        let kind = if self.emitters.is_background(li.light) {
+            Kind::Environment
+        } else {
+            Kind::Light(li.light)
+        };
+        let mut v = Vertex::endpoint(kind, li.p, li.n, li.le, li.weight());
+        v.pdf_fwd = v.pdf_light_origin(self);
+        Some(v)
+    }
+
+    //Balance heuristic weight of strategy (s, t) among all strategies that make
+    //the same path. sampled stands in for the connecting vertex of the s = 1 and
+    //t = 1 strategies.
+    fn mis_weight(
+        &self,
+        light: &[Vertex<'a>],
+        camera: &[Vertex<'a>],
+        sampled: Option<&Vertex<'a>>,
+        s: usize,
+        t: usize,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+        let pt = match (t, sampled) {
+            (1, Some(v)) => v,
+            _ => &camera[t - 1],
+        };
+        let qs = match (s, sampled) {
+            (0, _) => None,
+            (1, Some(v)) => Some(v),
+            _ => Some(&light[s - 1]),
+        };
+        let pt_minus = if t > 1 { Some(&camera[t - 2]) } else { None };
+        let qs_minus = if s > 1 { Some(&light[s - 2]) } else { None };
+
+        //(pdf_fwd, pdf_rev, delta) of the path as this strategy builds it
+        let densities = |v: &Vertex| (v.pdf_fwd, v.pdf_rev, v.delta);
+        let mut cam: Vec<_> = camera[..t].iter().map(densities).collect();
+        let mut lig: Vec<_> = light.iter().take(s).map(densities).collect();
+        lig.resize(s, (0.0, 0.0, false));
+        cam[t - 1] = densities(pt);
+        if let Some(qs) = qs {
+            lig[s - 1] = densities(qs);
+        }
+        //the connected vertices are never specular
+        cam[t - 1].2 = false;
+        cam[t - 1].1 = match qs {
+            Some(qs) => qs.pdf(self, qs_minus, pt),
+            None => pt.pdf_light_origin(self),
+        };
+        if let Some(pt_minus) = pt_minus {
+            cam[t - 2].1 = match qs {
+                Some(qs) => pt.pdf(self, Some(qs), pt_minus),
+                None => pt.pdf_light(self, pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            lig[s - 1].2 = false;
+            lig[s - 1].1 = pt.pdf(self, pt_minus, qs);
+            if let Some(qs_minus) = qs_minus {
+                lig[s - 2].1 = qs.pdf(self, Some(pt), qs_minus);
+            }
+        }
+
+        //ratios of the other strategies' densities to this one's, zero densities
+        //of specular bounces cancel
+        let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+        let mut sum = 0.0;
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap(cam[i].1) / remap(cam[i].0);
+            if !cam[i].2 && !cam[i - 1].2 {
+                sum += ri;
+            }
+        }
+        ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap(lig[i].1) / remap(lig[i].0);
+            let delta_before = i > 0 && lig[i - 1].2;
+            if !lig[i].2 && !delta_before {
+                sum += ri;
+            }
+        }
+        1.0 / (1.0 + sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::integrator::Integrator;
+    use crate::material::{Dielectric, DiffuseLight, Lambertian};
+    use crate::render::{mean_luminance, RenderSettings};
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            image_width: 32,
+            image_height: 24,
+            samples_per_pixel: 64,
+            max_depth: 5,
+            background: Vec3::zero(),
+            spectral: false,
+            noise_threshold: 0.0,
+            filter: Filter::pixel_box(),
+            split_lighting: false,
+            features: false,
+            integrator: Integrator::Path,
+        }
+    }
+
+    fn camera(settings: &RenderSettings) -> Camera {
+        let mut cam = Camera::new(
+            Vec3::new(0.0, 2.0, 6.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            50.0,
+            4.0 / 3.0,
+            0.0,
+            6.0,
+            0.0,
+            1.0,
+        );
+        cam.set_image_height(settings.image_height);
+        cam
+    }
+
+    #[test]
+    fn test_matches_path_tracing() {
+        //a ball on a floor under a small light, with light tracing reaching the
+        //floor and the ball from above
+        let grey = Lambertian::new(SolidColor::new_with_col(0.5, 0.5, 0.5));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, -100.0, 0.0),
+            100.0,
+            grey.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, grey)));
+        let light = DiffuseLight::new(SolidColor::new_with_col(20.0, 20.0, 20.0));
+        world.add(Arc::new(Sphere::new(Vec3::new(0.0, 3.5, 1.0), 0.5, light)));
+        let mut lights = HittableList::new();
+        lights.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, 3.5, 1.0),
+            0.5,
+            Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+        )));
+        let settings = settings();
+        let cam = camera(&settings);
+        let mean = |integrator| {
+            let settings = RenderSettings {
+                integrator,
+                ..settings
+            };
+            mean_luminance(&world, &lights, &cam, &settings, 5)
+        };
+        let path = mean(Integrator::Path);
+        let bdpt = mean(Integrator::Bdpt);
+        assert!(path > 0.05);
+        assert!((bdpt - path).abs() < 0.05 * path, "{} {}", bdpt, path);
+    }
+
+    #[test]
+    fn test_matches_path_tracing_through_glass() {
+        //the light sits inside a glass ball, every path to it is refracted
+        let grey = Lambertian::new(SolidColor::new_with_col(0.5, 0.5, 0.5));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, -100.0, 0.0),
+            100.0,
+            grey,
+        )));
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, 1.5, 0.0),
+            1.0,
+            Dielectric::new(1.5),
+        )));
+        let light = DiffuseLight::new(SolidColor::new_with_col(4.0, 4.0, 4.0));
+        world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.5, 0.0), 0.7, light)));
+        let mut lights = HittableList::new();
+        lights.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, 1.5, 0.0),
+            0.7,
+            Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+        )));
+        let settings = settings();
+        let cam = camera(&settings);
+        let mean = |integrator| {
+            let settings = RenderSettings {
+                integrator,
+                ..settings
+            };
+            mean_luminance(&world, &lights, &cam, &settings, 5)
+        };
+        let path = mean(Integrator::Path);
+        let bdpt = mean(Integrator::Bdpt);
+        assert!(path > 0.05);
+        assert!((bdpt - path).abs() < 0.05 * path, "{} {}", bdpt, path);
+    }
+}
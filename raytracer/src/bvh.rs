@@ -1,15 +1,19 @@
 #![allow(clippy::collapsible_else_if)]
 
 use crate::aabb::AABB;
+use crate::error::{RenderError, Result};
 use crate::hittable::{HitRecord, Hittable};
 use crate::hittable_list::HittableList;
 use crate::material::Lambertian;
 use crate::random_int;
 use crate::ray::Ray;
 use crate::sphere::Sphere;
+use crate::texture::SolidColor;
 use crate::vec3::Vec3;
+use std::cmp::Ordering;
 use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct BvhNode {
     pub left: Arc<dyn Hittable>,
     pub right: Arc<dyn Hittable>,
@@ -17,9 +21,20 @@ pub struct BvhNode {
 }
 
 impl BvhNode {
-    pub fn new_with_list(list: &mut HittableList, time0: f64, time1: f64) -> Self {
-        let len = list.objects.len();
-        Self::new_with_vec(&mut list.objects, 0, len, time0, time1)
+    //Moves the objects of list into the tree. Objects without a bounding box,
+    //like an Atmosphere, cannot be placed in it and are left in list for the
+    //caller to keep beside the tree.
+    pub fn new_with_list(list: &mut HittableList, time0: f64, time1: f64) -> Result<Self> {
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = list
+            .objects
+            .drain(..)
+            .partition(|object| object.bounding_box(time0, time1).is_some());
+        list.objects = unbounded;
+        if bounded.is_empty() {
+            return Err(RenderError::EmptyScene);
+        }
+        let len = bounded.len();
+        Ok(Self::new_with_vec(&mut bounded, 0, len, time0, time1))
     }
 
     pub fn new_with_vec(
@@ -33,12 +48,12 @@ impl BvhNode {
             left: Arc::new(Sphere::new(
                 Vec3::zero(),
                 0.0,
-                Arc::new(Lambertian::new_with_vec(Vec3::zero())),
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
             )),
             right: Arc::new(Sphere::new(
                 Vec3::zero(),
                 0.0,
-                Arc::new(Lambertian::new_with_vec(Vec3::zero())),
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
             )),
             my_box: AABB::new(Vec3::zero(), Vec3::zero()),
         };
@@ -56,25 +71,23 @@ impl BvhNode {
                 node.right = objects[start].clone();
             }
         } else {
-            objects.sort_by(|a, b| {
-                (Self::box_compare(&a, &b, axis).1)
-                    .partial_cmp(&Self::box_compare(&a, &b, axis).2)
-                    .unwrap()
+            objects[start..end].sort_by(|a, b| {
+                let (_, a, b) = Self::box_compare(a, b, axis);
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
             });
             let mid = start + object_span / 2;
             node.left = Arc::new(BvhNode::new_with_vec(objects, start, mid, time0, time1));
             node.right = Arc::new(BvhNode::new_with_vec(objects, mid, end, time0, time1));
         }
-        if let Some(box_left) = node.left.bounding_box(time0, time1) {
-            if let Some(box_right) = node.right.bounding_box(time0, time1) {
-                node.my_box = AABB::surrounding_box(&box_left, &box_right);
-                node
-            } else {
-                panic!("NO BOUNDING BOX IN bvhNode CONSTRUCTOR");
-            }
-        } else {
-            panic!("NO BOUNDING BOX IN bvhNode CONSTRUCTOR");
-        }
+        node.my_box = match (
+            node.left.bounding_box(time0, time1),
+            node.right.bounding_box(time0, time1),
+        ) {
+            (Some(box_left), Some(box_right)) => AABB::surrounding_box(&box_left, &box_right),
+            (Some(b), None) | (None, Some(b)) => b,
+            (None, None) => AABB::new(Vec3::zero(), Vec3::zero()),
+        };
+        node
     }
 
     pub fn box_compare(
@@ -82,40 +95,34 @@ impl BvhNode {
         b: &Arc<dyn Hittable>,
         axis: i32,
     ) -> (bool, f64, f64) {
-        let mut temp_a = 0.0;
-        let mut temp_b = 0.0;
-        if let Some(box_a) = a.bounding_box(0.0, 0.0) {
-            temp_a = box_a.minimum.at(axis);
-        } else {
-            panic!("NO BOUNDING BOX IN bvhNode CONSTRUCTOR");
-        }
-        if let Some(box_b) = b.bounding_box(0.0, 0.0) {
-            temp_b = box_b.minimum.at(axis);
-        } else {
-            panic!("NO BOUNDING BOX IN bvhNode CONSTRUCTOR");
-        }
+        //objects reaching here have boxes, see new_with_list
+        let temp_a = a
+            .bounding_box(0.0, 0.0)
+            .map_or(0.0, |box_a| box_a.minimum.at(axis));
+        let temp_b = b
+            .bounding_box(0.0, 0.0)
+            .map_or(0.0, |box_b| box_b.minimum.at(axis));
         (temp_a < temp_b, temp_a, temp_b)
     }
 }
 
 impl Hittable for BvhNode {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         if !self.my_box.hit(r, t_min, t_max) {
             return None;
         }
-        return if let Some(hit_left) = self.left.hit(r, t_min, t_max) {
-            if let Some(hit_right) = self.right.hit(r, t_min, hit_left.t) {
-                Some(hit_right)
-            } else {
-                Some(hit_left)
-            }
-        } else {
-            if let Some(hit_right) = self.right.hit(r, t_min, t_max) {
+        let hit_left = self.left.hit(r, t_min, t_max).map(|mut rec| {
+            rec.mix_object_id(0);
+            rec
+        });
+        let t_right = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+        match self.right.hit(r, t_min, t_right) {
+            Some(mut hit_right) => {
+                hit_right.mix_object_id(1);
                 Some(hit_right)
-            } else {
-                None
             }
-        };
+            None => hit_left,
+        }
     }
 
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
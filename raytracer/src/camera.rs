@@ -1,6 +1,6 @@
 use crate::vec3::Vec3;
 use crate::Ray;
-use crate::{degrees_to_radians, random_min_max};
+use crate::{degrees_to_radians, random_min_max, PI};
 
 #[derive(Clone, Copy)]
 pub struct Camera {
@@ -14,6 +14,7 @@ pub struct Camera {
     lens_radius: f64,
     time0: f64,
     time1: f64,
+    pixel_size: f64,
 }
 
 impl Camera {
@@ -48,21 +49,92 @@ impl Camera {
             lens_radius: aperture / 2.0,
             time0: _time0,
             time1: _time1,
+            pixel_size: 0.0,
         };
         cam.lower_left_corner =
             cam.origin - cam.horizontal / 2.0 - cam.vertical / 2.0 - w * focus_dist;
         cam
     }
 
+    //camera rays then carry a cone one pixel wide, used to filter textures
+    pub fn set_image_height(&mut self, image_height: u32) {
+        self.pixel_size = self.vertical.length() / image_height as f64;
+    }
+
+    //unit vector the camera looks along
+    pub fn view_direction(&self) -> Vec3 {
+        -self.w
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = Vec3::random_in_unit_disk() * self.lens_radius;
         let offset = self.u * rd.x + self.v * rd.y;
+        let dir =
+            self.lower_left_corner + self.horizontal * s + self.vertical * t - self.origin - offset;
         Ray {
             orig: self.origin + offset,
-            dir: self.lower_left_corner + self.horizontal * s + self.vertical * t
-                - self.origin
-                - offset,
+            dir,
             tm: random_min_max(self.time0, self.time1),
+            lambda: 0.0,
+            cone_width: 0.0,
+            cone_spread: self.pixel_size / dir.length(),
+        }
+    }
+
+    //The camera as seen by light paths, after Veach's thesis and pbrt: lens
+    //points are uniform over the lens disk, a pinhole counts as a lens of area 1.
+    pub fn lens_area(&self) -> f64 {
+        if self.lens_radius > 0.0 {
+            PI * self.lens_radius * self.lens_radius
+        } else {
+            1.0
+        }
+    }
+
+    pub fn sample_lens(&self) -> Vec3 {
+        let rd = Vec3::random_in_unit_disk() * self.lens_radius;
+        self.origin + self.u * rd.x + self.v * rd.y
+    }
+
+    //area of the image on a plane at distance 1 in front of the lens
+    fn image_area(&self) -> f64 {
+        let focus_dist = (self.origin - self.lower_left_corner) * self.w;
+        self.horizontal.length() * self.vertical.length() / (focus_dist * focus_dist)
+    }
+
+    //the (s, t) get_ray takes to send a ray from lens point o along dir, None
+    //outside the image
+    pub fn raster(&self, o: &Vec3, dir: &Vec3) -> Option<(f64, f64)> {
+        let focus_dist = (self.origin - self.lower_left_corner) * self.w;
+        let cos_theta = -(*dir * self.w);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        let d = *o + *dir * (focus_dist / cos_theta) - self.lower_left_corner;
+        let s = d * self.horizontal / self.horizontal.squared_length();
+        let t = d * self.vertical / self.vertical.squared_length();
+        if (0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&t) {
+            Some((s, t))
+        } else {
+            None
+        }
+    }
+
+    //importance W_e of a ray through the image along dir
+    pub fn importance(&self, dir: &Vec3) -> f64 {
+        let cos_theta = -(dir.unit() * self.w);
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        1.0 / (self.image_area() * self.lens_area() * cos_theta.powi(4))
+    }
+
+    //solid angle density of get_ray sending a ray from lens point o along dir
+    pub fn pdf_dir(&self, o: &Vec3, dir: &Vec3) -> f64 {
+        let dir = dir.unit();
+        match self.raster(o, &dir) {
+            Some(_) => 1.0 / (self.image_area() * (-(dir * self.w)).powi(3)),
+            None => 0.0,
         }
     }
 }
@@ -0,0 +1,312 @@
+use crate::error::{RenderError, Result};
+use crate::film::Film;
+use crate::filter::FilterKind;
+use crate::integrator::Integrator;
+use crate::render::RenderSettings;
+use crate::vec3::Vec3;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"RTCKPT06";
+//radiance sum, filter weight, luminance sum and its square, sample count,
+//albedo, normal, depth, position and uv sums, material and object id, light
+//path splats, followed by the lighting pass sums
+const PIXEL_BYTES: usize = 3 * 8 + 3 * 8 + 4 + 16 * 8 + 2 * 4;
+
+//Everything needed to carry on a progressive render: the accumulated film and
+//the random stream position. Every pass reseeds its tiles from the seed and the
+//pass number, so those two are the whole RNG state.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub settings_hash: u64,
+    pub seed: u64,
+    pub passes: u32,
+    pub film: Film,
+}
+
+impl Checkpoint {
+    //starts from an empty film, see render::new_film
+    pub fn new(settings_hash: u64, seed: u64, film: Film) -> Self {
+        Self {
+            settings_hash,
+            seed,
+            passes: 0,
+            film,
+        }
+    }
+
+    //writes to a temporary file first, an interrupted save keeps the old checkpoint
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let name = path.to_string_lossy();
+        let tmp = path.with_extension("ckpt.tmp");
+        let pixels = self.film.sum.len();
+        let lighting = self.film.lighting_passes;
+        let mut bytes = Vec::with_capacity(48 + pixels * (PIXEL_BYTES + lighting * 24));
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.settings_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.passes.to_le_bytes());
+        bytes.extend_from_slice(&self.film.width.to_le_bytes());
+        bytes.extend_from_slice(&self.film.height.to_le_bytes());
+        bytes.extend_from_slice(&(lighting as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.film.light_paths.to_le_bytes());
+        let film = &self.film;
+        let put_vec3 = |bytes: &mut Vec<u8>, v: Vec3| {
+            for c in [v.x, v.y, v.z].iter() {
+                bytes.extend_from_slice(&c.to_le_bytes());
+            }
+        };
+        for i in 0..pixels {
+            put_vec3(&mut bytes, film.sum[i]);
+            bytes.extend_from_slice(&film.weight[i].to_le_bytes());
+            bytes.extend_from_slice(&film.lum[i].to_le_bytes());
+            bytes.extend_from_slice(&film.lum_sq[i].to_le_bytes());
+            bytes.extend_from_slice(&film.samples[i].to_le_bytes());
+            put_vec3(&mut bytes, film.albedo[i]);
+            put_vec3(&mut bytes, film.normal[i]);
+            bytes.extend_from_slice(&film.depth[i].to_le_bytes());
+            put_vec3(&mut bytes, film.position[i]);
+            put_vec3(&mut bytes, film.uv[i]);
+            bytes.extend_from_slice(&film.material_id[i].to_le_bytes());
+            bytes.extend_from_slice(&film.object_id[i].to_le_bytes());
+            put_vec3(&mut bytes, film.splat[i]);
+            for k in 0..lighting {
+                put_vec3(&mut bytes, film.lighting[i * lighting + k]);
+            }
+        }
+        let mut file = fs::File::create(&tmp).map_err(|e| RenderError::io(&name, e))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| RenderError::io(&name, e))?;
+        fs::rename(&tmp, path).map_err(|e| RenderError::io(&name, e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let name = path.to_string_lossy();
+        let bytes = fs::read(path).map_err(|e| RenderError::io(&name, e))?;
+        let truncated = || RenderError::invalid_data(&name, "checkpoint is truncated");
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(RenderError::invalid_data(&name, "not a render checkpoint"));
+        }
+        let mut reader = Reader {
+            bytes: &bytes,
+            pos: MAGIC.len(),
+        };
+        let settings_hash = reader.u64().ok_or_else(truncated)?;
+        let seed = reader.u64().ok_or_else(truncated)?;
+        let passes = reader.u32().ok_or_else(truncated)?;
+        let width = reader.u32().ok_or_else(truncated)?;
+        let height = reader.u32().ok_or_else(truncated)?;
+        let lighting = reader.u32().ok_or_else(truncated)? as usize;
+        let light_paths = reader.u64().ok_or_else(truncated)?;
+        let pixels = width as usize * height as usize;
+        if bytes.len() - reader.pos != pixels * (PIXEL_BYTES + lighting * 24) {
+            return Err(truncated());
+        }
+        let mut film = Film::with_lighting(width, height, lighting);
+        film.light_paths = light_paths;
+        for i in 0..pixels {
+            film.sum[i] = reader.vec3().ok_or_else(truncated)?;
+            film.weight[i] = reader.f64().ok_or_else(truncated)?;
+            film.lum[i] = reader.f64().ok_or_else(truncated)?;
+            film.lum_sq[i] = reader.f64().ok_or_else(truncated)?;
+            film.samples[i] = reader.u32().ok_or_else(truncated)?;
+            film.albedo[i] = reader.vec3().ok_or_else(truncated)?;
+            film.normal[i] = reader.vec3().ok_or_else(truncated)?;
+            film.depth[i] = reader.f64().ok_or_else(truncated)?;
+            film.position[i] = reader.vec3().ok_or_else(truncated)?;
+            film.uv[i] = reader.vec3().ok_or_else(truncated)?;
+            film.material_id[i] = reader.u32().ok_or_else(truncated)?;
+            film.object_id[i] = reader.u32().ok_or_else(truncated)?;
+            film.splat[i] = reader.vec3().ok_or_else(truncated)?;
+            for k in 0..lighting {
+                film.lighting[i * lighting + k] = reader.vec3().ok_or_else(truncated)?;
+            }
+        }
+        Ok(Self {
+            settings_hash,
+            seed,
+            passes,
+            film,
+        })
+    }
+}
+
+//FNV-1a over everything that changes the image except the sample count, which a
+//resumed render may raise
+pub fn settings_hash(scene: u32, settings: &RenderSettings) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for b in bytes {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    feed(&scene.to_le_bytes());
+    feed(&settings.image_width.to_le_bytes());
+    feed(&settings.image_height.to_le_bytes());
+    feed(&settings.max_depth.to_le_bytes());
+    feed(&settings.background.x.to_bits().to_le_bytes());
+    feed(&settings.background.y.to_bits().to_le_bytes());
+    feed(&settings.background.z.to_bits().to_le_bytes());
+    let (integrator, count) = match settings.integrator {
+        Integrator::Path => (0, 0),
+        Integrator::Bdpt => (1, 0),
+        Integrator::Photon { photons } => (2, photons),
+        Integrator::Mlt { chains } => (3, chains),
+    };
+    feed(&[
+        settings.spectral as u8,
+        settings.split_lighting as u8,
+        settings.features as u8,
+        integrator,
+    ]);
+    feed(&count.to_le_bytes());
+    //adaptive sampling stops pixels at a different count per threshold
+    feed(&settings.noise_threshold.to_bits().to_le_bytes());
+    let filter = settings.filter;
+    let (tag, p0, p1) = match filter.kind {
+        FilterKind::Box => (0, 0.0, 0.0),
+        FilterKind::Tent => (1, 0.0, 0.0),
+        FilterKind::Gaussian { sigma } => (2, sigma, 0.0),
+        FilterKind::Mitchell { b, c } => (3, b, c),
+        FilterKind::Lanczos { tau } => (4, tau, 0.0),
+    };
+    feed(&[tag]);
+    for x in [filter.radius, p0, p1].iter() {
+        feed(&x.to_bits().to_le_bytes());
+    }
+    hash
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(slice)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(buf))
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.u64().map(f64::from_bits)
+    }
+
+    fn vec3(&mut self) -> Option<Vec3> {
+        Some(Vec3::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::hittable_list::HittableList;
+    use crate::material::Lambertian;
+    use crate::render::{progress_bar, render_pass};
+    use crate::sphere::Sphere;
+    use crate::temp_path;
+    use crate::texture::SolidColor;
+    use std::sync::Arc;
+
+    fn small_scene() -> (HittableList, Camera, RenderSettings) {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::zero(),
+            1.0,
+            Lambertian::new(SolidColor::new_with_vec(Vec3::new(0.5, 0.5, 0.5))),
+        )));
+        let settings = RenderSettings {
+            image_width: 40,
+            image_height: 30,
+            samples_per_pixel: 32,
+            max_depth: 5,
+            background: Vec3::new(0.7, 0.8, 1.0),
+            spectral: false,
+            noise_threshold: 0.0,
+            //overlapping tile margins must not break reproducibility
+            filter: "mitchell".parse().unwrap(),
+            split_lighting: false,
+            features: false,
+            integrator: Integrator::Path,
+        };
+        let mut cam = Camera::new(
+            Vec3::new(0.0, 0.0, 4.0),
+            Vec3::zero(),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            4.0 / 3.0,
+            0.0,
+            4.0,
+            0.0,
+            1.0,
+        );
+        cam.set_image_height(settings.image_height);
+        (world, cam, settings)
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted() {
+        let (world, cam, settings) = small_scene();
+        let lights = HittableList::new();
+        let bar = progress_bar(&settings, &Film::new(40, 30));
+        bar.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+
+        let mut whole = Checkpoint::new(settings_hash(0, &settings), 7, Film::new(40, 30));
+        while render_pass(&world, &lights, &cam, &settings, &mut whole, &bar) {}
+        assert_eq!(whole.passes, 2);
+
+        let path = temp_path("resume.ckpt");
+        let mut first = Checkpoint::new(settings_hash(0, &settings), 7, Film::new(40, 30));
+        render_pass(&world, &lights, &cam, &settings, &mut first, &bar);
+        first.save(&path).unwrap();
+        let mut resumed = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.settings_hash, first.settings_hash);
+        assert_eq!(resumed.film.samples(0, 0), 16);
+        while render_pass(&world, &lights, &cam, &settings, &mut resumed, &bar) {}
+
+        assert_eq!(resumed.film.total_samples(), 32 * 40 * 30);
+        for (a, b) in whole.film.sum.iter().zip(resumed.film.sum.iter()) {
+            assert_eq!((a.x, a.y, a.z), (b.x, b.y, b.z));
+        }
+    }
+
+    #[test]
+    fn test_hash_covers_noise_threshold() {
+        let (_, _, settings) = small_scene();
+        let adaptive = RenderSettings {
+            noise_threshold: 0.01,
+            ..settings
+        };
+        assert_ne!(settings_hash(0, &settings), settings_hash(0, &adaptive));
+    }
+
+    #[test]
+    fn test_reject_truncated() {
+        let path = temp_path("truncated.ckpt");
+        Checkpoint::new(1, 2, Film::with_lighting(4, 4, 2))
+            .save(&path)
+            .unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -7,6 +7,7 @@ use crate::rectangle::{XyRect, XzRect, YzRect};
 use crate::vec3::Vec3;
 use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct Box {
     pub box_min: Vec3,
     pub box_max: Vec3,
@@ -14,7 +15,7 @@ pub struct Box {
 }
 
 impl Box {
-    pub fn new(p0: Vec3, p1: Vec3, ptr: Arc<dyn Material>) -> Self {
+    pub fn new<T: 'static + Material + Clone + Sync + Send>(p0: Vec3, p1: Vec3, ptr: T) -> Self {
         let mut _box = Self {
             box_min: p0,
             box_max: p1,
@@ -73,8 +74,12 @@ impl Box {
 }
 
 impl Hittable for Box {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.sides.hit(r, t_min, t_max)
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        //the six sides are one object
+        self.sides.hit(r, t_min, t_max).map(|mut rec| {
+            rec.object_id = 0;
+            rec
+        })
     }
 
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
@@ -0,0 +1,316 @@
+use crate::film::{Features, Film};
+use crate::vec3::Vec3;
+use std::str::FromStr;
+
+//edge stopping strengths for luminance (in standard deviations), normals and
+//relative depth
+const SIGMA_L: f64 = 4.0;
+const SIGMA_N: i32 = 64;
+const SIGMA_Z: f64 = 0.02;
+//B3 spline taps of the à-trous filter and the number of its passes
+const ATROUS_TAPS: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+const ATROUS_PASSES: u32 = 5;
+const BILATERAL_RADIUS: i32 = 6;
+
+//Post-process denoisers guided by the film's albedo, normal and depth buffers
+//and its per-pixel variance. Radiance is divided by albedo before filtering so
+//texture detail survives, and multiplied back afterwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Denoiser {
+    //edge-avoiding à-trous wavelet filter, Dammertz et al. 2010, with the
+    //variance guided luminance weight of SVGF
+    ATrous,
+    //one wide joint bilateral pass
+    Bilateral,
+}
+
+impl Denoiser {
+    //filtered radiance of the film, row by row
+    pub fn denoise(&self, film: &Film) -> Vec<Vec3> {
+        let guide = Guide::new(film);
+        let mut color = Vec::with_capacity(guide.features.len());
+        let mut variance = Vec::with_capacity(guide.features.len());
+        for y in 0..film.height {
+            for x in 0..film.width {
+                let c = film.pixel(x, y);
+                let c = if c.x.is_finite() && c.y.is_finite() && c.z.is_finite() {
+                    c
+                } else {
+                    Vec3::zero()
+                };
+                let f = demodulation(&film.features(x, y));
+                color.push(Vec3::new(c.x / f.x, c.y / f.y, c.z / f.z));
+                //film.variance is INF for pixels with fewer than two samples
+                variance.push(if film.samples(x, y) < 2 {
+                    None
+                } else {
+                    Some(film.variance(x, y) / f.luminance().powi(2))
+                });
+            }
+        }
+        let variance = finite_variance(film.width as i32, film.height as i32, &variance);
+        let mut variance = variance;
+        match self {
+            Denoiser::ATrous => {
+                for i in 0..ATROUS_PASSES {
+                    let (c, v) = guide.pass(&color, &variance, 1 << i, &ATROUS_TAPS);
+                    color = c;
+                    variance = v;
+                }
+            }
+            Denoiser::Bilateral => {
+                let taps: Vec<f64> = (-BILATERAL_RADIUS..=BILATERAL_RADIUS)
+                    .map(|i| (-(i * i) as f64 / 18.0).exp())
+                    .collect();
+                color = guide.pass(&color, &variance, 1, &taps).0;
+            }
+        }
+        color
+            .iter()
+            .zip(guide.features.iter())
+            .map(|(c, features)| Vec3::elemul(*c, demodulation(features)))
+            .collect()
+    }
+}
+
+impl FromStr for Denoiser {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "atrous" => Ok(Denoiser::ATrous),
+            "bilateral" => Ok(Denoiser::Bilateral),
+            _ => Err(format!("unknown denoiser {}", s)),
+        }
+    }
+}
+
+//Pixels without a variance estimate would make the sums of every pass they
+//reach infinite or NaN. They take the largest variance around them, or in the
+//image if their neighbours have none either.
+fn finite_variance(width: i32, height: i32, variance: &[Option<f64>]) -> Vec<f64> {
+    let known = |v: &Option<f64>| v.filter(|v| v.is_finite());
+    let image_max = variance.iter().filter_map(known).fold(0.0, f64::max);
+    let mut out = Vec::with_capacity(variance.len());
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(v) = known(&variance[(y * width + x) as usize]) {
+                out.push(v);
+                continue;
+            }
+            let mut around = None;
+            for qy in (y - 1).max(0)..(y + 2).min(height) {
+                for qx in (x - 1).max(0)..(x + 2).min(width) {
+                    if let Some(q) = known(&variance[(qy * width + qx) as usize]) {
+                        around = Some(around.map_or(q, |m: f64| m.max(q)));
+                    }
+                }
+            }
+            out.push(around.unwrap_or(image_max));
+        }
+    }
+    out
+}
+
+//albedo channels radiance is divided by, floored so dark channels do not blow
+//up their noise
+fn demodulation(features: &Features) -> Vec3 {
+    let a = features.albedo;
+    Vec3::new(a.x.max(0.01), a.y.max(0.01), a.z.max(0.01))
+}
+
+struct Guide {
+    width: i32,
+    height: i32,
+    features: Vec<Features>,
+}
+
+impl Guide {
+    fn new(film: &Film) -> Self {
+        let mut features = Vec::with_capacity((film.width * film.height) as usize);
+        for y in 0..film.height {
+            for x in 0..film.width {
+                let mut f = film.features(x, y);
+                f.normal = f.normal.unit();
+                features.push(f);
+            }
+        }
+        Self {
+            width: film.width as i32,
+            height: film.height as i32,
+            features,
+        }
+    }
+
+    //One filter pass with the separable taps spread step pixels apart. Returns
+    //the filtered radiance and the variance of it.
+    fn pass(
+        &self,
+        color: &[Vec3],
+        variance: &[f64],
+        step: i32,
+        taps: &[f64],
+    ) -> (Vec<Vec3>, Vec<f64>) {
+        let r = taps.len() as i32 / 2;
+        let blurred = self.blur_variance(variance);
+        let mut out_color = Vec::with_capacity(color.len());
+        let mut out_variance = Vec::with_capacity(color.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let p = (y * self.width + x) as usize;
+                let (fp, lp) = (&self.features[p], color[p].luminance());
+                let l_scale = SIGMA_L * blurred[p].sqrt() + 1e-4;
+                let mut sum = Vec3::zero();
+                let (mut weights, mut sum_variance) = (0.0, 0.0);
+                for j in -r..=r {
+                    for i in -r..=r {
+                        let (qx, qy) = (x + i * step, y + j * step);
+                        if qx < 0 || qx >= self.width || qy < 0 || qy >= self.height {
+                            continue;
+                        }
+                        let q = (qy * self.width + qx) as usize;
+                        let fq = &self.features[q];
+                        let w = taps[(i + r) as usize]
+                            * taps[(j + r) as usize]
+                            * (-(lp - color[q].luminance()).abs() / l_scale).exp()
+                            * normal_weight(fp.normal, fq.normal)
+                            * depth_weight(fp.depth, fq.depth, step);
+                        sum += color[q] * w;
+                        weights += w;
+                        sum_variance += w * w * variance[q];
+                    }
+                }
+                //the centre tap always has weight
+                out_color.push(sum / weights);
+                out_variance.push(sum_variance / (weights * weights));
+            }
+        }
+        (out_color, out_variance)
+    }
+
+    //3x3 Gaussian of the variance, a pixel whose few samples happened to agree
+    //would otherwise refuse all its neighbours
+    fn blur_variance(&self, variance: &[f64]) -> Vec<f64> {
+        let taps = [0.25, 0.5, 0.25];
+        let mut out = Vec::with_capacity(variance.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (mut sum, mut weights) = (0.0, 0.0);
+                for j in -1..=1 {
+                    for i in -1..=1 {
+                        let (qx, qy) = (x + i, y + j);
+                        if qx >= 0 && qx < self.width && qy >= 0 && qy < self.height {
+                            let w = taps[(i + 1) as usize] * taps[(j + 1) as usize];
+                            sum += w * variance[(qy * self.width + qx) as usize];
+                            weights += w;
+                        }
+                    }
+                }
+                out.push(sum / weights);
+            }
+        }
+        out
+    }
+}
+
+//misses have no normal and only blend with other misses
+fn normal_weight(np: Vec3, nq: Vec3) -> f64 {
+    match (np.near_zero(), nq.near_zero()) {
+        (true, true) => 1.0,
+        (false, false) => (np * nq).max(0.0).powi(SIGMA_N),
+        _ => 0.0,
+    }
+}
+
+//depth differences relative to the distance, allowed to grow with the step to
+//follow slanted surfaces
+fn depth_weight(zp: f64, zq: f64, step: i32) -> f64 {
+    (-(zp - zq).abs() / (SIGMA_Z * step as f64 * zp.max(zq) + 1e-6)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::{random_0_1, seed_random, INF};
+
+    #[test]
+    fn test_single_sample_pixels_stay_finite() {
+        let mut film = Film::new(8, 8);
+        let features = Features::background(Vec3::ones());
+        for y in 0..8 {
+            for x in 0..8 {
+                //one sample in the corner, three everywhere else
+                let n = if x == 0 && y == 0 { 1 } else { 3 };
+                for k in 0..n {
+                    let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                    let c = Vec3::ones() * (0.2 + 0.1 * k as f64);
+                    film.add_sample(px, py, c, &[], &features, &Filter::pixel_box());
+                }
+            }
+        }
+        assert_eq!(film.variance(0, 0), INF);
+        for denoiser in [Denoiser::ATrous, Denoiser::Bilateral].iter() {
+            for c in denoiser.denoise(&film).iter() {
+                assert!(c.x.is_finite() && c.y.is_finite() && c.z.is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn test_denoise_keeps_edges() {
+        //two walls meeting at x = 16, each flat grey under noise
+        seed_random(11);
+        let mut film = Film::new(32, 32);
+        let truth = |x: u32| if x < 16 { 0.2 } else { 0.6 };
+        for y in 0..32 {
+            for x in 0..32 {
+                let features = Features {
+                    normal: if x < 16 {
+                        Vec3::new(0.0, 0.0, 1.0)
+                    } else {
+                        Vec3::new(1.0, 0.0, 0.0)
+                    },
+                    depth: 5.0,
+                    ..Features::background(Vec3::ones())
+                };
+                for _ in 0..4 {
+                    let c = truth(x) * 2.0 * random_0_1();
+                    let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                    film.add_sample(
+                        px,
+                        py,
+                        Vec3::ones() * c,
+                        &[],
+                        &features,
+                        &Filter::pixel_box(),
+                    );
+                }
+            }
+        }
+        let error = |pixels: &[Vec3]| {
+            pixels
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.x - truth(i as u32 % 32)).abs())
+                .sum::<f64>()
+                / pixels.len() as f64
+        };
+        let noisy = error(&film.pixels());
+        for denoiser in [Denoiser::ATrous, Denoiser::Bilateral].iter() {
+            let denoised = denoiser.denoise(&film);
+            assert!(error(&denoised) < noisy / 2.0, "{:?}", denoiser);
+            //nothing bleeds across the crease
+            assert!(
+                (denoised[16 * 32 + 15].x - 0.2).abs() < 0.1,
+                "{:?}",
+                denoiser
+            );
+            assert!(
+                (denoised[16 * 32 + 16].x - 0.6).abs() < 0.2,
+                "{:?}",
+                denoiser
+            );
+        }
+    }
+}
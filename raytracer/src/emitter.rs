@@ -0,0 +1,271 @@
+use crate::aabb::AABB;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::onb::ONB;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::{random_0_1, INF, PI};
+
+//points per light used to estimate its power for light selection
+const POWER_SAMPLES: usize = 16;
+//relative distance a hit may be off the sampled light point and still be it
+const HIT_EPS: f64 = 1e-4;
+
+//The light sources of a scene, for integrators that start paths at them: the
+//entries of the lights list, whose stand-in shapes are looked through for the
+//emitting material, and the background, shining into the scene's bounding
+//sphere. Lights are picked in proportion to their estimated power.
+pub struct Emitters<'a> {
+    world: &'a HittableList,
+    pub lights: &'a HittableList,
+    pub background: Vec3,
+    //probability of picking each light and, last, the background
+    pub light_pdf: Vec<f64>,
+    pub center: Vec3,
+    pub radius: f64,
+}
+
+//a ray leaving a light and the densities it was sampled with
+pub struct Emission {
+    //index into light_pdf
+    pub light: usize,
+    pub ray: Ray,
+    //normal of the emitting side, zero for the background
+    pub n: Vec3,
+    pub le: Vec3,
+    pub select: f64,
+    //area density of the origin and solid angle density of the direction
+    pub pdf_pos: f64,
+    pub pdf_dir: f64,
+}
+
+impl Emission {
+    //flux the ray carries
+    pub fn beta(&self) -> Vec3 {
+        let cosine = if self.n == Vec3::zero() {
+            1.0
+        } else {
+            self.n * self.ray.dir
+        };
+        self.le * cosine / (self.select * self.pdf_pos * self.pdf_dir)
+    }
+}
+
+//light arriving at a point from a sampled light, unoccluded
+pub struct Incident {
+    //index into light_pdf
+    pub light: usize,
+    //the point on the light and its normal, for the background a point beyond
+    //the scene and zero
+    pub p: Vec3,
+    pub n: Vec3,
+    pub dir: Vec3,
+    pub le: Vec3,
+    pub select: f64,
+    //solid angle density at the receiving point
+    pub pdf: f64,
+}
+
+impl Incident {
+    //le over its density
+    pub fn weight(&self) -> Vec3 {
+        self.le / (self.select * self.pdf)
+    }
+}
+
+//Centre and radius of the scene's bounding sphere. Unbounded objects like an
+//Atmosphere have no bounding box, the bounds are the rest's.
+pub fn scene_bounds(world: &HittableList) -> (Vec3, f64) {
+    let bbox = world
+        .objects
+        .iter()
+        .filter_map(|object| object.bounding_box(0.0, 1.0))
+        .fold(None, |bounds: Option<AABB>, b| match bounds {
+            Some(a) => Some(AABB::surrounding_box(&a, &b)),
+            None => Some(b),
+        });
+    match bbox {
+        Some(bbox) => {
+            let center = (bbox.minimum + bbox.maximum) * 0.5;
+            (center, (bbox.maximum - center).length())
+        }
+        None => (Vec3::zero(), 0.0),
+    }
+}
+
+impl<'a> Emitters<'a> {
+    pub fn new(world: &'a HittableList, lights: &'a HittableList, background: Vec3) -> Self {
+        let mut emitters = Self::with_light_pdf(world, lights, background, Vec::new());
+        let radius = emitters.radius;
+        //power of a one sided emitter is pi * area * radiance
+        let mut power: Vec<f64> = lights
+            .objects
+            .iter()
+            .map(|light| {
+                let area = light.area();
+                if area <= 0.0 {
+                    return 0.0;
+                }
+                let mut radiance = 0.0;
+                for _ in 0..POWER_SAMPLES {
+                    let (p, n) = light.sample_surface();
+                    radiance += emitters.emission(p, n, 0.0).luminance();
+                    radiance += emitters.emission(p, -n, 0.0).luminance();
+                }
+                PI * area * radiance / POWER_SAMPLES as f64
+            })
+            .collect();
+        power.push(PI * radius * radius * background.luminance());
+        let total: f64 = power.iter().sum();
+        emitters.light_pdf = power
+            .iter()
+            .map(|p| if total > 0.0 { p / total } else { 0.0 })
+            .collect();
+        emitters
+    }
+
+    //the emitters picked by a light_pdf estimated before, by Emitters::new
+    pub fn with_light_pdf(
+        world: &'a HittableList,
+        lights: &'a HittableList,
+        background: Vec3,
+        light_pdf: Vec<f64>,
+    ) -> Self {
+        let (center, radius) = scene_bounds(world);
+        Self {
+            world,
+            lights,
+            background,
+            light_pdf,
+            center,
+            radius,
+        }
+    }
+
+    pub fn is_background(&self, light: usize) -> bool {
+        light == self.lights.objects.len()
+    }
+
+    //where points of the background are put, beyond the scene
+    pub fn far(&self) -> f64 {
+        2.0 * self.radius + 1.0
+    }
+
+    //Light leaving the emitter at p along dir. The lights list only holds
+    //stand-ins, the emitting material is found by looking back at p from dir.
+    pub fn emission(&self, p: Vec3, dir: Vec3, tm: f64) -> Vec3 {
+        let eps = 1e-4 * (1.0 + p.length());
+        let probe = Ray::new(p + dir * eps, -dir, tm);
+        match self.world.hit(&probe, 0.5 * eps, 1.5 * eps) {
+            Some(rec) => Vec3::elemul(
+                rec.mat_ptr.emitted(&probe, &rec, rec.u, rec.v, &rec.p),
+                rec.weight,
+            ),
+            None => Vec3::zero(),
+        }
+    }
+
+    //index into light_pdf, None when nothing emits
+    pub fn pick(&self) -> Option<usize> {
+        let mut left = random_0_1();
+        for (k, pdf) in self.light_pdf.iter().enumerate() {
+            if *pdf > 0.0 && left < *pdf {
+                return Some(k);
+            }
+            left -= pdf;
+        }
+        self.light_pdf.iter().rposition(|pdf| *pdf > 0.0)
+    }
+
+    //A ray leaving a light. Area lights emit cosine distributed from a random
+    //side, None when that side is dark. The background sends parallel rays
+    //from a disk of the bounding sphere.
+    pub fn sample_emission(&self, tm: f64) -> Option<Emission> {
+        let light = self.pick()?;
+        let select = self.light_pdf[light];
+        if self.is_background(light) {
+            let dir = Vec3::random_unit_vector();
+            let mut uvw = ONB::new();
+            uvw.build_from_w(dir);
+            let d = Vec3::random_in_unit_disk();
+            let origin = self.center + (uvw.u * d.x + uvw.v * d.y - dir) * self.radius;
+            return Some(Emission {
+                light,
+                ray: Ray::new(origin, dir, tm),
+                n: Vec3::zero(),
+                le: self.background,
+                select,
+                pdf_pos: 1.0 / (PI * self.radius * self.radius),
+                pdf_dir: 1.0 / (4.0 * PI),
+            });
+        }
+        let shape = &self.lights.objects[light];
+        let (p, n) = shape.sample_surface();
+        let n = if random_0_1() < 0.5 { n } else { -n };
+        let mut uvw = ONB::new();
+        uvw.build_from_w(n);
+        let dir = uvw.local_with_vec(Vec3::random_cosine_direction());
+        let le = self.emission(p, dir, tm);
+        let cosine = n * dir;
+        if le == Vec3::zero() || cosine <= 0.0 {
+            return None;
+        }
+        Some(Emission {
+            light,
+            ray: Ray::new(p, dir, tm),
+            n,
+            le,
+            select,
+            pdf_pos: 1.0 / shape.area(),
+            pdf_dir: 0.5 * cosine / PI,
+        })
+    }
+
+    //A light seen from p, None when it is occluded or dark. The background is
+    //sampled uniformly over directions.
+    pub fn sample_incident(&self, p: Vec3, tm: f64) -> Option<Incident> {
+        let light = self.pick()?;
+        let select = self.light_pdf[light];
+        if self.is_background(light) {
+            let dir = Vec3::random_unit_vector();
+            if self.world.hit(&Ray::new(p, dir, tm), 0.001, INF).is_some() {
+                return None;
+            }
+            return Some(Incident {
+                light,
+                p: p + dir * self.far(),
+                n: Vec3::zero(),
+                dir,
+                le: self.background,
+                select,
+                pdf: 1.0 / (4.0 * PI),
+            });
+        }
+        let shape = &self.lights.objects[light];
+        let (y, _) = shape.sample_surface();
+        let dist = (y - p).length();
+        let r = Ray::new(p, (y - p) / dist, tm);
+        //the first hit has to be the light itself
+        let rec = self.world.hit(&r, 0.001, INF)?;
+        if (rec.t - dist).abs() > HIT_EPS * dist {
+            return None;
+        }
+        let le = Vec3::elemul(
+            rec.mat_ptr.emitted(&r, &rec, rec.u, rec.v, &rec.p),
+            rec.weight,
+        );
+        let cosine = (rec.normal * r.dir).abs();
+        if le == Vec3::zero() || cosine == 0.0 {
+            return None;
+        }
+        Some(Incident {
+            light,
+            p: rec.p,
+            n: rec.normal,
+            dir: r.dir,
+            le,
+            select,
+            pdf: dist * dist / (cosine * shape.area()),
+        })
+    }
+}
@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+//errors from loading scene assets and writing the output image
+#[derive(Debug)]
+pub enum RenderError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    Image {
+        path: String,
+        source: image::ImageError,
+    },
+    #[cfg(feature = "exr")]
+    Exr {
+        path: String,
+        source: exr::error::Error,
+    },
+    //an EXR image was asked for in a build without the exr feature
+    ExrDisabled {
+        path: String,
+    },
+    InvalidData {
+        path: String,
+        reason: String,
+    },
+    EmptyScene,
+}
+
+pub type Result<T> = std::result::Result<T, RenderError>;
+
+impl RenderError {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        RenderError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn image(path: &str, source: image::ImageError) -> Self {
+        RenderError::Image {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    #[cfg(feature = "exr")]
+    pub fn exr(path: &str, source: exr::error::Error) -> Self {
+        RenderError::Exr {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn exr_disabled(path: &str) -> Self {
+        RenderError::ExrDisabled {
+            path: path.to_string(),
+        }
+    }
+
+    pub fn invalid_data(path: &str, reason: &str) -> Self {
+        RenderError::InvalidData {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Io { path, source } => write!(f, "cannot access {}: {}", path, source),
+            RenderError::Image { path, source } => {
+                write!(f, "cannot read image {}: {}", path, source)
+            }
+            #[cfg(feature = "exr")]
+            RenderError::Exr { path, source } => {
+                write!(f, "cannot access EXR image {}: {}", path, source)
+            }
+            RenderError::ExrDisabled { path } => write!(
+                f,
+                "cannot access EXR image {}: built without the exr feature",
+                path
+            ),
+            RenderError::InvalidData { path, reason } => write!(f, "{}: {}", path, reason),
+            RenderError::EmptyScene => write!(f, "no object with a bounding box to build a BVH of"),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Io { source, .. } => Some(source),
+            RenderError::Image { source, .. } => Some(source),
+            #[cfg(feature = "exr")]
+            RenderError::Exr { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
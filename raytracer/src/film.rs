@@ -0,0 +1,410 @@
+use crate::filter::Filter;
+use crate::texture::linear_to_srgb;
+use crate::vec3::Vec3;
+use crate::{clamp, INF};
+use image::{ImageBuffer, RgbImage};
+
+//surface data at a camera ray's first hit, see integrator::first_hit_features
+#[derive(Clone, Copy, Debug)]
+pub struct Features {
+    pub albedo: Vec3,
+    pub normal: Vec3,
+    pub depth: f64,
+    pub position: Vec3,
+    pub u: f64,
+    pub v: f64,
+    pub material_id: u32,
+    pub object_id: u32,
+}
+
+impl Features {
+    //what a camera ray that escapes sees
+    pub fn background(background: Vec3) -> Self {
+        Self {
+            albedo: background,
+            normal: Vec3::zero(),
+            depth: 0.0,
+            position: Vec3::zero(),
+            u: 0.0,
+            v: 0.0,
+            material_id: 0,
+            object_id: 0,
+        }
+    }
+}
+
+//pixels whose filter weights add up to no more are black, negative filter lobes
+//can cancel a pixel's weight to about zero or below
+const MIN_WEIGHT: f64 = 1e-6;
+
+//light a light path carried into the camera, landing on pixel (x, y) of the
+//image, see bdpt
+#[derive(Clone, Copy, Debug)]
+pub struct Splat {
+    pub x: u32,
+    pub y: u32,
+    pub color: Vec3,
+}
+
+//Float framebuffer, y = 0 is the top row. Pixels accumulate the filter weighted
+//radiance of the samples splatted into them and the weights. The pixel a sample
+//falls in also counts it, sums its luminance and squared luminance for a
+//variance estimate and sums its features, IDs are those of its first sample.
+//Optional lighting passes are splatted like the radiance. Light path splats
+//are kept apart and averaged over all light paths traced into the film.
+#[derive(Clone)]
+pub struct Film {
+    pub width: u32,
+    pub height: u32,
+    pub(crate) sum: Vec<Vec3>,
+    pub(crate) weight: Vec<f64>,
+    pub(crate) lum: Vec<f64>,
+    pub(crate) lum_sq: Vec<f64>,
+    pub(crate) samples: Vec<u32>,
+    pub(crate) albedo: Vec<Vec3>,
+    pub(crate) normal: Vec<Vec3>,
+    pub(crate) depth: Vec<f64>,
+    pub(crate) position: Vec<Vec3>,
+    pub(crate) uv: Vec<Vec3>,
+    pub(crate) material_id: Vec<u32>,
+    pub(crate) object_id: Vec<u32>,
+    pub(crate) lighting_passes: usize,
+    pub(crate) lighting: Vec<Vec3>,
+    pub(crate) splat: Vec<Vec3>,
+    pub(crate) light_paths: u64,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_lighting(width, height, 0)
+    }
+
+    //with room for that many lighting passes, see integrator::ray_color
+    pub fn with_lighting(width: u32, height: u32, lighting_passes: usize) -> Self {
+        let n = (width * height) as usize;
+        Self {
+            width,
+            height,
+            sum: vec![Vec3::zero(); n],
+            weight: vec![0.0; n],
+            lum: vec![0.0; n],
+            lum_sq: vec![0.0; n],
+            samples: vec![0; n],
+            albedo: vec![Vec3::zero(); n],
+            normal: vec![Vec3::zero(); n],
+            depth: vec![0.0; n],
+            position: vec![Vec3::zero(); n],
+            uv: vec![Vec3::zero(); n],
+            material_id: vec![0; n],
+            object_id: vec![0; n],
+            lighting_passes,
+            lighting: vec![Vec3::zero(); n * lighting_passes],
+            splat: vec![Vec3::zero(); n],
+            light_paths: 0,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    //Adds a sample taken at (px, py) in continuous pixel coordinates, pixel
+    //(x, y) spans [x, x + 1) x [y, y + 1). Neighbours beyond the film's edge
+    //miss out on their share, so tiles come with a margin of filter radius.
+    //lighting holds the sample's lighting passes, or nothing for films without.
+    pub fn add_sample(
+        &mut self,
+        px: f64,
+        py: f64,
+        color: Vec3,
+        lighting: &[Vec3],
+        features: &Features,
+        filter: &Filter,
+    ) {
+        //x + random_0_1() can round up to x + 1
+        let x = (px as u32).min(self.width - 1);
+        let y = (py as u32).min(self.height - 1);
+        let i = self.index(x, y);
+        let l = color.luminance();
+        self.lum[i] += l;
+        self.lum_sq[i] += l * l;
+        if self.samples[i] == 0 {
+            self.material_id[i] = features.material_id;
+            self.object_id[i] = features.object_id;
+        }
+        self.samples[i] += 1;
+        self.albedo[i] += features.albedo;
+        self.normal[i] += features.normal;
+        self.depth[i] += features.depth;
+        self.position[i] += features.position;
+        self.uv[i] += Vec3::new(features.u, features.v, 0.0);
+        let passes = if lighting.is_empty() {
+            0
+        } else {
+            self.lighting_passes
+        };
+
+        let r = filter.radius;
+        let x0 = (px - 0.5 - r).ceil().max(0.0) as u32;
+        let x1 = ((px - 0.5 + r).floor() as i64).min(self.width as i64 - 1);
+        let y0 = (py - 0.5 - r).ceil().max(0.0) as u32;
+        let y1 = ((py - 0.5 + r).floor() as i64).min(self.height as i64 - 1);
+        for y in y0 as i64..=y1 {
+            for x in x0 as i64..=x1 {
+                let w = filter.eval(x as f64 + 0.5 - px, y as f64 + 0.5 - py);
+                if w != 0.0 {
+                    let i = self.index(x as u32, y as u32);
+                    self.sum[i] += color * w;
+                    self.weight[i] += w;
+                    let pixel = &mut self.lighting[i * passes..(i + 1) * passes];
+                    for (sum, light) in pixel.iter_mut().zip(lighting) {
+                        *sum += *light * w;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn add_splat(&mut self, splat: &Splat) {
+        let i = self.index(splat.x, splat.y);
+        self.splat[i] += splat.color;
+    }
+
+    //light paths the splats are averaged over, one per camera sample with bdpt
+    pub fn add_light_paths(&mut self, n: u64) {
+        self.light_paths += n;
+    }
+
+    //adds a film rendered for the region whose top left pixel is (x0, y0)
+    pub fn merge(&mut self, x0: u32, y0: u32, tile: &Film) {
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let (i, j) = (self.index(x0 + x, y0 + y), tile.index(x, y));
+                if self.samples[i] == 0 {
+                    self.material_id[i] = tile.material_id[j];
+                    self.object_id[i] = tile.object_id[j];
+                }
+                self.sum[i] += tile.sum[j];
+                self.weight[i] += tile.weight[j];
+                self.lum[i] += tile.lum[j];
+                self.lum_sq[i] += tile.lum_sq[j];
+                self.samples[i] += tile.samples[j];
+                self.albedo[i] += tile.albedo[j];
+                self.normal[i] += tile.normal[j];
+                self.depth[i] += tile.depth[j];
+                self.position[i] += tile.position[j];
+                self.uv[i] += tile.uv[j];
+                self.splat[i] += tile.splat[j];
+                let (n, m) = (self.lighting_passes, tile.lighting_passes);
+                for k in 0..n.min(m) {
+                    self.lighting[i * n + k] += tile.lighting[j * m + k];
+                }
+            }
+        }
+        self.light_paths += tile.light_paths;
+    }
+
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.samples.iter().map(|n| *n as u64).sum()
+    }
+
+    //variance of the mean luminance of the pixel's own samples, infinite below
+    //two samples
+    pub fn variance(&self, x: u32, y: u32) -> f64 {
+        let i = self.index(x, y);
+        let n = self.samples[i] as f64;
+        if n < 2.0 {
+            return INF;
+        }
+        let mean = self.lum[i] / n;
+        ((self.lum_sq[i] - mean * mean * n) / (n - 1.0)).max(0.0) / n
+    }
+
+    //standard error of the pixel's mean luminance as it shows after the sRGB
+    //encoding of the output
+    pub fn error(&self, x: u32, y: u32) -> f64 {
+        let n = self.samples(x, y) as f64;
+        let mean = if n > 0.0 {
+            self.lum[self.index(x, y)] / n
+        } else {
+            0.0
+        };
+        //scaled by the slope of linear_to_srgb, which the linear toe bounds
+        let slope = if mean <= 0.0031308 {
+            12.92
+        } else {
+            1.055 / 2.4 * mean.powf(1.0 / 2.4 - 1.0)
+        };
+        self.variance(x, y).sqrt() * slope
+    }
+
+    //mean features of the pixel's samples, normals stay unnormalized
+    pub fn features(&self, x: u32, y: u32) -> Features {
+        let i = self.index(x, y);
+        let n = self.samples[i].max(1) as f64;
+        Features {
+            albedo: self.albedo[i] / n,
+            normal: self.normal[i] / n,
+            depth: self.depth[i] / n,
+            position: self.position[i] / n,
+            u: self.uv[i].x / n,
+            v: self.uv[i].y / n,
+            material_id: self.material_id[i],
+            object_id: self.object_id[i],
+        }
+    }
+
+    pub fn lighting_passes(&self) -> usize {
+        self.lighting_passes
+    }
+
+    //filtered radiance of lighting pass k
+    pub fn lighting(&self, x: u32, y: u32, k: usize) -> Vec3 {
+        let i = self.index(x, y);
+        if self.weight[i] <= MIN_WEIGHT {
+            Vec3::zero()
+        } else {
+            self.lighting[i * self.lighting_passes + k] / self.weight[i]
+        }
+    }
+
+    //filtered radiance plus the light path splats, black for pixels nothing reached
+    pub fn pixel(&self, x: u32, y: u32) -> Vec3 {
+        let i = self.index(x, y);
+        let filtered = if self.weight[i] <= MIN_WEIGHT {
+            Vec3::zero()
+        } else {
+            self.sum[i] / self.weight[i]
+        };
+        if self.light_paths == 0 {
+            filtered
+        } else {
+            //a light path reaches any pixel, per pixel that is light_paths / pixels paths
+            let pixels = self.width as f64 * self.height as f64;
+            filtered + self.splat[i] * (pixels / self.light_paths as f64)
+        }
+    }
+
+    pub fn pixels(&self) -> Vec<Vec3> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y))
+            .collect()
+    }
+
+    pub fn to_rgb_image(&self) -> RgbImage {
+        to_rgb_image(self.width, self.height, &self.pixels())
+    }
+
+    //samples per pixel relative to the busiest pixel, from black through red to
+    //pale yellow
+    pub fn sample_heatmap(&self) -> RgbImage {
+        let max = self.samples.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let stops = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.3, 0.0, 0.6),
+            Vec3::new(0.9, 0.2, 0.1),
+            Vec3::new(1.0, 1.0, 0.6),
+        ];
+        let mut img: RgbImage = ImageBuffer::new(self.width, self.height);
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            let t = self.samples[self.index(x, y)] as f64 / max * 3.0;
+            let k = (t as usize).min(2);
+            let c = stops[k] * (1.0 - (t - k as f64)) + stops[k + 1] * (t - k as f64);
+            *pixel = image::Rgb([
+                (c.x * 255.0) as u8,
+                (c.y * 255.0) as u8,
+                (c.z * 255.0) as u8,
+            ]);
+        }
+        img
+    }
+}
+
+//encodes rows of linear radiance as 8 bit sRGB
+pub fn to_rgb_image(width: u32, height: u32, pixels: &[Vec3]) -> RgbImage {
+    let mut img: RgbImage = ImageBuffer::new(width, height);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let c = pixels[(y * width + x) as usize];
+        //negative filter lobes can ring below zero next to bright edges
+        let mut color = Vec3::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0));
+        write_color(&mut color, 1, pixel);
+    }
+    img
+}
+
+//averages the samples of a pixel and encodes it as 8 bit sRGB, the curve
+//image textures are decoded with
+pub fn write_color(color: &mut Vec3, samples_per_pixel: i32, pixel: &mut image::Rgb<u8>) {
+    let mut r = color.x;
+    let mut g = color.y;
+    let mut b = color.z;
+    //deal with NAN
+    if r != r {
+        r = 0.0;
+    }
+    if g != g {
+        g = 0.0;
+    }
+    if b != b {
+        b = 0.0;
+    }
+    let scale = 1.0 / samples_per_pixel as f64;
+    r = clamp(linear_to_srgb(scale * r), 0.0, 0.999) * 256.0;
+    g = clamp(linear_to_srgb(scale * g), 0.0, 0.999) * 256.0;
+    b = clamp(linear_to_srgb(scale * b), 0.0, 0.999) * 256.0;
+    *pixel = image::Rgb([r as u8, g as u8, b as u8]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::srgb_to_linear;
+
+    #[test]
+    fn test_write_color_round_trips_texture_decode() {
+        for v in [0.0, 0.002, 0.05, 0.2, 0.5, 0.9].iter() {
+            let mut pixel = image::Rgb([0, 0, 0]);
+            write_color(&mut (Vec3::ones() * *v), 1, &mut pixel);
+            let decoded = srgb_to_linear(pixel[0] as f32 / 255.0) as f64;
+            assert!(
+                (decoded - v).abs() < 0.01 * v.max(0.1),
+                "{} -> {}",
+                v,
+                decoded
+            );
+        }
+    }
+
+    #[test]
+    fn test_splat_reaches_neighbours() {
+        let mut film = Film::new(5, 5);
+        let features = Features::background(Vec3::ones());
+        film.add_sample(2.5, 2.5, Vec3::ones(), &[], &features, &Filter::pixel_box());
+        assert_eq!(film.weight.iter().filter(|w| **w > 0.0).count(), 1);
+
+        let tent: Filter = "tent:1.5".parse().unwrap();
+        film.add_sample(2.25, 2.5, Vec3::ones(), &[], &features, &tent);
+        assert_eq!(film.samples(2, 2), 2);
+        assert_eq!(film.samples(1, 2), 0);
+        //pixel centres 1.5, 2.5 and 3.5 are within reach, 0.5 is not
+        assert!(film.pixel(1, 2).x > 0.0 && film.pixel(3, 3).x > 0.0);
+        assert_eq!(film.weight[film.index(0, 2)], 0.0);
+        assert!(film.weight[film.index(1, 2)] > film.weight[film.index(3, 2)]);
+    }
+
+    #[test]
+    fn test_cancelled_weight_is_black() {
+        let mut film = Film::new(3, 1);
+        let features = Features::background(Vec3::ones());
+        let mitchell: Filter = "mitchell:2".parse().unwrap();
+        //a sample only reaches pixel 0 through the filter's negative lobe
+        film.add_sample(2.4, 0.5, Vec3::ones(), &[], &features, &mitchell);
+        assert!(film.weight[0] < 0.0);
+        assert_eq!(film.pixel(0, 0), Vec3::zero());
+    }
+}
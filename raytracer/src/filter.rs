@@ -0,0 +1,153 @@
+use crate::PI;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    //standard deviation as a fraction of the radius
+    Gaussian { sigma: f64 },
+    //B = C = 1/3 is the pair Mitchell and Netravali recommend
+    Mitchell { b: f64, c: f64 },
+    //sinc windowed by a wider sinc, tau lobes out to the radius
+    Lanczos { tau: f64 },
+}
+
+//Pixel reconstruction filter. Every sample is splatted into all pixels whose
+//centre lies within radius of it, weighted by the filter, and pixels divide by
+//the weights they received.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub radius: f64,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        Self { kind, radius }
+    }
+
+    //every sample counts for the pixel it falls in only
+    pub fn pixel_box() -> Self {
+        Self::new(FilterKind::Box, 0.5)
+    }
+
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    fn eval_1d(&self, x: f64) -> f64 {
+        let (x, r) = (x.abs(), self.radius);
+        if x > r {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => r - x,
+            FilterKind::Gaussian { sigma } => {
+                let sigma = sigma * r;
+                let g = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                //shifted to reach zero at the radius
+                (g(x) - g(r)).max(0.0)
+            }
+            FilterKind::Mitchell { b, c } => {
+                let x = 2.0 * x / r;
+                if x > 1.0 {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                }
+            }
+            FilterKind::Lanczos { tau } => {
+                let x = x / r * tau;
+                sinc(x) * sinc(x / tau)
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+//"name" or "name:radius", e.g. "mitchell:2"
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let radius = match parts.next() {
+            Some(r) => match r.parse::<f64>() {
+                Ok(r) if r > 0.0 => Some(r),
+                _ => return Err(format!("bad filter radius {}", r)),
+            },
+            None => None,
+        };
+        let (kind, default_radius) = match name {
+            "box" => (FilterKind::Box, 0.5),
+            "tent" => (FilterKind::Tent, 1.0),
+            "gaussian" => (FilterKind::Gaussian { sigma: 1.0 / 3.0 }, 1.5),
+            "mitchell" => (
+                FilterKind::Mitchell {
+                    b: 1.0 / 3.0,
+                    c: 1.0 / 3.0,
+                },
+                2.0,
+            ),
+            "lanczos" => (FilterKind::Lanczos { tau: 3.0 }, 3.0),
+            _ => return Err(format!("unknown filter {}", name)),
+        };
+        Ok(Self::new(kind, radius.unwrap_or(default_radius)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_support() {
+        for name in ["box", "tent", "gaussian", "mitchell", "lanczos"].iter() {
+            let filter: Filter = name.parse().unwrap();
+            assert!(filter.eval(0.0, 0.0) > 0.0, "{}", name);
+            assert_eq!(filter.eval(filter.radius + 0.01, 0.0), 0.0, "{}", name);
+            assert_eq!(filter.eval(0.3, -0.2), filter.eval(-0.3, 0.2), "{}", name);
+        }
+        //the sharpening filters have negative lobes
+        let mitchell: Filter = "mitchell".parse().unwrap();
+        assert!(mitchell.eval(1.5, 0.0) < 0.0);
+        let lanczos: Filter = "lanczos:3".parse().unwrap();
+        assert!(lanczos.eval(1.5, 0.0) < 0.0);
+        assert!("tent:0".parse::<Filter>().is_err());
+        assert!("sinc".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn test_filter_scales_with_radius() {
+        for name in ["tent", "gaussian", "mitchell", "lanczos"].iter() {
+            let shape: Vec<f64> = [1.0, 2.5, 3.0]
+                .iter()
+                .map(|r| {
+                    let filter: Filter = format!("{}:{}", name, r).parse().unwrap();
+                    //no cut off lobe at the radius
+                    assert!(filter.eval_1d(filter.radius).abs() < 1e-9, "{}:{}", name, r);
+                    filter.eval_1d(0.4 * r) / filter.eval_1d(0.0)
+                })
+                .collect();
+            assert!((shape[0] - shape[1]).abs() < 1e-9, "{}", name);
+            assert!((shape[0] - shape[2]).abs() < 1e-9, "{}", name);
+        }
+    }
+}
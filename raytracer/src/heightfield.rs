@@ -0,0 +1,404 @@
+use crate::aabb::AABB;
+use crate::error::{RenderError, Result};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::perlin::Perlin;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct Heightfield<T: Material> {
+    pub heights: Arc<Vec<f64>>,
+    pub normals: Arc<Vec<Vec3>>,
+    pub nx: usize,
+    pub nz: usize,
+    pub x0: f64,
+    pub x1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub y_min: f64,
+    pub y_max: f64,
+    pub mat_ptr: T,
+}
+
+impl<T: Material> Heightfield<T> {
+    //heights[j * nx + i] is the sample at x = x0 + i * dx, z = z0 + j * dz
+    pub fn new(
+        heights: Vec<f64>,
+        nx: usize,
+        nz: usize,
+        _x0: f64,
+        _x1: f64,
+        _z0: f64,
+        _z1: f64,
+        mat: T,
+    ) -> Result<Self> {
+        if nx < 2 || nz < 2 {
+            return Err(RenderError::invalid_data(
+                "heightfield",
+                "a heightfield needs at least 2x2 samples",
+            ));
+        }
+        if heights.len() != nx * nz {
+            return Err(RenderError::invalid_data(
+                "heightfield",
+                "number of heights does not match the grid size",
+            ));
+        }
+        let mut y_min = f64::MAX;
+        let mut y_max = f64::MIN;
+        for h in heights.iter() {
+            y_min = y_min.min(*h);
+            y_max = y_max.max(*h);
+        }
+        let dx = (_x1 - _x0) / (nx - 1) as f64;
+        let dz = (_z1 - _z0) / (nz - 1) as f64;
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                //central differences, one-sided at the border
+                let il = if i > 0 { i - 1 } else { i };
+                let ir = if i + 1 < nx { i + 1 } else { i };
+                let jl = if j > 0 { j - 1 } else { j };
+                let jr = if j + 1 < nz { j + 1 } else { j };
+                let dhdx = (heights[j * nx + ir] - heights[j * nx + il]) / ((ir - il) as f64 * dx);
+                let dhdz = (heights[jr * nx + i] - heights[jl * nx + i]) / ((jr - jl) as f64 * dz);
+                normals.push(Vec3::new(-dhdx, 1.0, -dhdz).unit());
+            }
+        }
+        Ok(Self {
+            heights: Arc::new(heights),
+            normals: Arc::new(normals),
+            nx,
+            nz,
+            x0: _x0,
+            x1: _x1,
+            z0: _z0,
+            z1: _z1,
+            y_min,
+            y_max,
+            mat_ptr: mat,
+        })
+    }
+
+    //image row 0 is placed at z1 so that the same image used as an ImageTexture lines up
+    pub fn new_with_image(
+        filename: &str,
+        _x0: f64,
+        _x1: f64,
+        _z0: f64,
+        _z1: f64,
+        _y0: f64,
+        _y1: f64,
+        mat: T,
+    ) -> Result<Self> {
+        let img = image::open(Path::new(filename))
+            .map_err(|e| RenderError::image(filename, e))?
+            .to_luma8();
+        let (nx, nz) = (img.width() as usize, img.height() as usize);
+        if nx < 2 || nz < 2 {
+            return Err(RenderError::invalid_data(
+                filename,
+                "a heightfield needs at least 2x2 samples",
+            ));
+        }
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let luma = img.get_pixel(i as u32, (nz - 1 - j) as u32)[0] as f64 / 255.0;
+                heights.push(_y0 + luma * (_y1 - _y0));
+            }
+        }
+        Self::new(heights, nx, nz, _x0, _x1, _z0, _z1, mat)
+    }
+
+    pub fn new_with_perlin(
+        noise: &Perlin,
+        resolution: usize,
+        _x0: f64,
+        _x1: f64,
+        _z0: f64,
+        _z1: f64,
+        scale: f64,
+        height: f64,
+        mat: T,
+    ) -> Result<Self> {
+        let mut heights = Vec::with_capacity(resolution * resolution);
+        for j in 0..resolution {
+            for i in 0..resolution {
+                let x = _x0 + (_x1 - _x0) * i as f64 / (resolution - 1) as f64;
+                let z = _z0 + (_z1 - _z0) * j as f64 / (resolution - 1) as f64;
+                heights.push(height * noise.turb(&(Vec3::new(x, 0.0, z) * scale), 7));
+            }
+        }
+        Self::new(heights, resolution, resolution, _x0, _x1, _z0, _z1, mat)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        Vec3::new(
+            self.x0 + (self.x1 - self.x0) * i as f64 / (self.nx - 1) as f64,
+            self.heights[j * self.nx + i],
+            self.z0 + (self.z1 - self.z0) * j as f64 / (self.nz - 1) as f64,
+        )
+    }
+
+    //Moller-Trumbore, returns (t, b1, b2)
+    fn hit_triangle(
+        r: &Ray,
+        a: Vec3,
+        b: Vec3,
+        c: Vec3,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, f64, f64)> {
+        let e1 = b - a;
+        let e2 = c - a;
+        let pvec = Vec3::cross(r.dir, e2);
+        let det = e1 * pvec;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = r.orig - a;
+        let b1 = (tvec * pvec) * inv_det;
+        if !(-1e-9..=1.0 + 1e-9).contains(&b1) {
+            return None;
+        }
+        let qvec = Vec3::cross(tvec, e1);
+        let b2 = (r.dir * qvec) * inv_det;
+        if b2 < -1e-9 || b1 + b2 > 1.0 + 1e-9 {
+            return None;
+        }
+        let t = (e2 * qvec) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        Some((t, b1, b2))
+    }
+
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        let (i00, i10, i01, i11) = (
+            j * self.nx + i,
+            j * self.nx + i + 1,
+            (j + 1) * self.nx + i,
+            (j + 1) * self.nx + i + 1,
+        );
+        let p00 = self.vertex(i, j);
+        let p10 = self.vertex(i + 1, j);
+        let p01 = self.vertex(i, j + 1);
+        let p11 = self.vertex(i + 1, j + 1);
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut t_hit = t_max;
+        if let Some((t, b1, b2)) = Self::hit_triangle(r, p00, p10, p11, t_min, t_hit) {
+            t_hit = t;
+            let n = self.normals[i00] * (1.0 - b1 - b2)
+                + self.normals[i10] * b1
+                + self.normals[i11] * b2;
+            closest = Some((t, n));
+        }
+        if let Some((t, b1, b2)) = Self::hit_triangle(r, p00, p11, p01, t_min, t_hit) {
+            let n = self.normals[i00] * (1.0 - b1 - b2)
+                + self.normals[i11] * b1
+                + self.normals[i01] * b2;
+            closest = Some((t, n));
+        }
+        closest
+    }
+}
+
+impl<T: 'static + Clone + Material + Send + Sync> Hittable for Heightfield<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        //clip the ray against the bounding slab first
+        let mut t_enter = t_min;
+        let mut t_exit = t_max;
+        let lo = [self.x0, self.y_min, self.z0];
+        let hi = [self.x1, self.y_max, self.z1];
+        for axis in 0..3 {
+            let o = r.orig.at(axis);
+            let d = r.dir.at(axis);
+            let (lo, hi) = (lo[axis as usize], hi[axis as usize]);
+            if d.abs() < 1e-12 {
+                if o < lo || o > hi {
+                    return None;
+                }
+                continue;
+            }
+            let mut t0 = (lo - o) / d;
+            let mut t1 = (hi - o) / d;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_enter = t_enter.max(t0);
+            t_exit = t_exit.min(t1);
+            if t_exit < t_enter {
+                return None;
+            }
+        }
+
+        //2D DDA over the (nx - 1) x (nz - 1) cells in the xz plane
+        let dx = (self.x1 - self.x0) / (self.nx - 1) as f64;
+        let dz = (self.z1 - self.z0) / (self.nz - 1) as f64;
+        let start = r.at(t_enter);
+        let cell = |v: f64, v0: f64, d: f64, n: usize| -> i64 {
+            (((v - v0) / d).floor() as i64).max(0).min(n as i64 - 2)
+        };
+        let mut i = cell(start.x, self.x0, dx, self.nx);
+        let mut j = cell(start.z, self.z0, dz, self.nz);
+        let step_i: i64 = if r.dir.x > 0.0 { 1 } else { -1 };
+        let step_j: i64 = if r.dir.z > 0.0 { 1 } else { -1 };
+        let next_boundary = |idx: i64, step: i64, v0: f64, d: f64, o: f64, dir: f64| -> f64 {
+            if dir.abs() < 1e-12 {
+                return f64::MAX;
+            }
+            let edge = v0 + (idx + if step > 0 { 1 } else { 0 }) as f64 * d;
+            (edge - o) / dir
+        };
+        let mut t_next_i = next_boundary(i, step_i, self.x0, dx, r.orig.x, r.dir.x);
+        let mut t_next_j = next_boundary(j, step_j, self.z0, dz, r.orig.z, r.dir.z);
+        let t_delta_i = if r.dir.x.abs() < 1e-12 {
+            f64::MAX
+        } else {
+            dx / r.dir.x.abs()
+        };
+        let t_delta_j = if r.dir.z.abs() < 1e-12 {
+            f64::MAX
+        } else {
+            dz / r.dir.z.abs()
+        };
+
+        let mut t_cur = t_enter;
+        loop {
+            let t_cell_end = t_next_i.min(t_next_j).min(t_exit);
+            let (ci, cj) = (i as usize, j as usize);
+            //skip cells whose height range the ray segment cannot reach
+            let y_a = r.orig.y + r.dir.y * t_cur;
+            let y_b = r.orig.y + r.dir.y * t_cell_end;
+            let h = [
+                self.heights[cj * self.nx + ci],
+                self.heights[cj * self.nx + ci + 1],
+                self.heights[(cj + 1) * self.nx + ci],
+                self.heights[(cj + 1) * self.nx + ci + 1],
+            ];
+            let cell_min = h.iter().cloned().fold(f64::MAX, f64::min);
+            let cell_max = h.iter().cloned().fold(f64::MIN, f64::max);
+            if y_a.min(y_b) <= cell_max && y_a.max(y_b) >= cell_min {
+                if let Some((t, normal)) = self.hit_cell(r, ci, cj, t_min, t_max) {
+                    let p = r.at(t);
+                    let (width, depth) = (self.x1 - self.x0, self.z1 - self.z0);
+                    let mut rec = HitRecord {
+                        p,
+                        normal: Vec3::zero(),
+                        t,
+                        front_face: false,
+                        mat_ptr: &self.mat_ptr,
+                        u: (p.x - self.x0) / width,
+                        v: (p.z - self.z0) / depth,
+                        dpdu: Vec3::new(width, 0.0, 0.0),
+                        dpdv: Vec3::new(0.0, 0.0, depth),
+                        footprint: 0.0,
+                        incident: None,
+                        weight: Vec3::ones(),
+                        object_id: 0,
+                    };
+                    rec.set_face_normal(r, &normal.unit());
+                    return Some(rec);
+                }
+            }
+            if t_cell_end >= t_exit {
+                return None;
+            }
+            if t_next_i < t_next_j {
+                i += step_i;
+                if i < 0 || i > self.nx as i64 - 2 {
+                    return None;
+                }
+                t_cur = t_next_i;
+                t_next_i += t_delta_i;
+            } else {
+                j += step_j;
+                if j < 0 || j > self.nz as i64 - 2 {
+                    return None;
+                }
+                t_cur = t_next_j;
+                t_next_j += t_delta_j;
+            }
+        }
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        let output_box = AABB::new(
+            Vec3::new(self.x0, self.y_min - 0.0001, self.z0),
+            Vec3::new(self.x1, self.y_max + 0.0001, self.z1),
+        );
+        Some(output_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::SolidColor;
+
+    fn ramp() -> Heightfield<Lambertian<SolidColor>> {
+        //h = x over [0, 4] x [0, 4]
+        let mut heights = vec![];
+        for _j in 0..5 {
+            for i in 0..5 {
+                heights.push(i as f64);
+            }
+        }
+        Heightfield::new(
+            heights,
+            5,
+            5,
+            0.0,
+            4.0,
+            0.0,
+            4.0,
+            Lambertian::new(SolidColor::new_with_vec(Vec3::ones())),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_hit_from_above() {
+        let field = ramp();
+        let r = Ray::new(Vec3::new(2.5, 10.0, 1.5), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = field.hit(&r, 0.001, f64::MAX).unwrap();
+        assert!((rec.p.y - 2.5).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(-1.0, 1.0, 0.0).unit()).length() < 1e-9);
+        assert!((rec.u - 0.625).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bad_grid_is_an_error() {
+        let mat = || Lambertian::new(SolidColor::new_with_vec(Vec3::ones()));
+        let flat = |heights: Vec<f64>, nx, nz| {
+            Heightfield::new(heights, nx, nz, 0.0, 1.0, 0.0, 1.0, mat()).is_err()
+        };
+        assert!(flat(vec![0.0; 3], 1, 3));
+        assert!(flat(vec![0.0; 5], 2, 3));
+        assert!(Heightfield::new_with_perlin(
+            &Perlin::new(),
+            1,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+            1.0,
+            1.0,
+            mat()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_grazing_miss() {
+        let field = ramp();
+        let r = Ray::new(Vec3::new(-1.0, 4.5, 2.0), Vec3::new(1.0, 0.0, 0.1), 0.0);
+        assert!(field.hit(&r, 0.001, f64::MAX).is_none());
+    }
+}
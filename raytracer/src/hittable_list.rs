@@ -1,8 +1,8 @@
 use crate::aabb::AABB;
-use crate::hittable_static::{HitRecord, Hittable};
-use crate::random_int;
+use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
+use crate::{random_0_1, random_int};
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -25,22 +25,17 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut rec = HitRecord::new();
-        let mut hit_anything = false;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut rec = None;
         let mut closest_so_far = t_max;
-        for object in self.objects.iter() {
-            if let Some(temp_rec) = object.hit(r, t_min, closest_so_far) {
-                hit_anything = true;
+        for (i, object) in self.objects.iter().enumerate() {
+            if let Some(mut temp_rec) = object.hit(r, t_min, closest_so_far) {
                 closest_so_far = temp_rec.t;
-                rec = temp_rec.clone();
+                temp_rec.mix_object_id(i);
+                rec = Some(temp_rec);
             }
         }
-        if hit_anything {
-            Some(rec)
-        } else {
-            None
-        }
+        rec
     }
 
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
@@ -77,4 +72,24 @@ impl Hittable for HittableList {
         let int_len = self.objects.len() as i32;
         self.objects[random_int(0, int_len - 1) as usize].random(o)
     }
+
+    fn area(&self) -> f64 {
+        self.objects.iter().map(|object| object.area()).sum()
+    }
+
+    //picks the object by area, so points are uniform over the whole list
+    fn sample_surface(&self) -> (Vec3, Vec3) {
+        let mut left = random_0_1() * self.area();
+        for object in self.objects.iter() {
+            let area = object.area();
+            if left < area {
+                return object.sample_surface();
+            }
+            left -= area;
+        }
+        match self.objects.iter().rev().find(|object| object.area() > 0.0) {
+            Some(object) => object.sample_surface(),
+            None => (Vec3::zero(), Vec3::new(0.0, 1.0, 0.0)),
+        }
+    }
 }
@@ -0,0 +1,272 @@
+use crate::film::Features;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::pdf::{HittablePDF, MixturePDF, PDF};
+use crate::ray::Ray;
+use crate::spectrum::{SampledSpectrum, SampledWavelengths};
+use crate::vec3::Vec3;
+use crate::INF;
+use std::str::FromStr;
+use std::sync::Arc;
+
+//how render_tile estimates the light along camera rays
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    //unidirectional path tracing with light sampling, ray_color
+    Path,
+    //bidirectional path tracing with light tracing splats, bdpt::Bdpt
+    Bdpt,
+    //photon mapping with final gathering, photon::PhotonMapper, shooting this
+    //many photons every pass
+    Photon { photons: u32 },
+    //primary sample space Metropolis light transport over bidirectional paths,
+    //mlt::render_pass, running this many Markov chains
+    Mlt { chains: u32 },
+}
+
+//photons a pass shoots when "photon" is given without a count
+pub const DEFAULT_PHOTONS: u32 = 200_000;
+//chains Metropolis runs when "mlt" is given without a count
+pub const DEFAULT_CHAINS: u32 = 64;
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    //path, bdpt, photon[:N] or mlt[:CHAINS]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let count = match parts.next() {
+            Some(n) if name == "photon" || name == "mlt" => match n.parse() {
+                Ok(n) if n > 0 => Some(n),
+                _ => return Err(format!("invalid {} count {}", name, n)),
+            },
+            Some(_) => return Err(format!("integrator {} takes no parameter", name)),
+            None => None,
+        };
+        match name {
+            "path" => Ok(Integrator::Path),
+            "bdpt" => Ok(Integrator::Bdpt),
+            "photon" => Ok(Integrator::Photon {
+                photons: count.unwrap_or(DEFAULT_PHOTONS),
+            }),
+            "mlt" => Ok(Integrator::Mlt {
+                chains: count.unwrap_or(DEFAULT_CHAINS),
+            }),
+            _ => Err(format!("unknown integrator {}", s)),
+        }
+    }
+}
+
+pub fn ray_color_spectral(
+    r: &Ray,
+    wavelengths: &mut SampledWavelengths,
+    background: &Vec3,
+    world: &HittableList,
+    lights: &HittableList,
+    depth: i32,
+) -> SampledSpectrum {
+    if depth <= 0 {
+        return SampledSpectrum::zero();
+    }
+    return if let Some(mut rec) = world.hit(r, 0.001, INF) {
+        rec.set_footprint(r);
+        let r_in = rec.incident.unwrap_or(*r);
+        let emitted = SampledSpectrum::from_rgb(
+            Vec3::elemul(
+                rec.weight,
+                rec.mat_ptr.emitted(&r_in, &rec, rec.u, rec.v, &rec.p),
+            ),
+            wavelengths,
+        );
+        if rec.mat_ptr.is_wavelength_dependent() {
+            wavelengths.terminate_secondary();
+        }
+        if let Some(s_rec) = rec.mat_ptr.scatter(&r_in, &rec) {
+            let attenuation =
+                SampledSpectrum::from_rgb(Vec3::elemul(s_rec.attenuation, rec.weight), wavelengths);
+            if s_rec.is_specular {
+                let mut specular_ray = Ray::new_with_lambda(
+                    s_rec.specular_ray.orig,
+                    s_rec.specular_ray.dir,
+                    s_rec.specular_ray.tm,
+                    r.lambda,
+                );
+                specular_ray.inherit_cone(r, rec.t);
+                emitted
+                    + attenuation
+                        * ray_color_spectral(
+                            &specular_ray,
+                            wavelengths,
+                            background,
+                            world,
+                            lights,
+                            depth - 1,
+                        )
+            } else {
+                let (direction, pdf_val) = sample_direction(lights, &rec.p, s_rec.pdf_ptr);
+                let mut scattered = Ray::new_with_lambda(rec.p, direction, r.tm, r.lambda);
+                scattered.inherit_cone(r, rec.t);
+                emitted
+                    + attenuation
+                        * ray_color_spectral(
+                            &scattered,
+                            wavelengths,
+                            background,
+                            world,
+                            lights,
+                            depth - 1,
+                        )
+                        * (rec.mat_ptr.scattering_value(&r_in, &rec, &scattered) / pdf_val)
+            }
+        } else {
+            emitted
+        }
+    } else {
+        SampledSpectrum::from_rgb(*background, wavelengths)
+    };
+}
+
+//slots of ray_color's lighting passes
+pub const EMISSION: usize = 0;
+pub const DIRECT: usize = 1;
+pub const INDIRECT: usize = 2;
+pub const FIRST_LIGHT: usize = 3;
+
+//3 bounce slots, one per light, one for other emitters and one for the background
+pub fn lighting_passes(lights: &HittableList) -> usize {
+    FIRST_LIGHT + lights.objects.len() + 2
+}
+
+//Path traced radiance along r, sampling the lights and the material alike.
+//Given lighting_passes(lights) slots in passes the estimate is also split
+//twice: EMISSION, DIRECT and INDIRECT hold the light that reached the camera
+//after 0, 1 or more scattering events, from FIRST_LIGHT on it is split by
+//where it was emitted: each entry of lights, then other emitters, then the
+//background. Either group sums to the result. Empty passes skip the split.
+pub fn ray_color(
+    r: &Ray,
+    background: &Vec3,
+    world: &HittableList,
+    lights: &HittableList,
+    depth: i32,
+    passes: &mut [Vec3],
+) -> Vec3 {
+    let n_lights = lights.objects.len();
+    let split = !passes.is_empty();
+    let mut color = Vec3::zero();
+    let mut add = |bounce: i32, source: usize, light: Vec3| {
+        color += light;
+        if split {
+            passes[(bounce as usize).min(INDIRECT)] += light;
+            passes[FIRST_LIGHT + source] += light;
+        }
+    };
+    let mut ray = *r;
+    let mut throughput = Vec3::ones();
+    for bounce in 0..depth.max(0) {
+        let mut rec = match world.hit(&ray, 0.001, INF) {
+            Some(rec) => rec,
+            None => {
+                add(bounce, n_lights + 1, Vec3::elemul(throughput, *background));
+                break;
+            }
+        };
+        rec.set_footprint(&ray);
+        //a walk inside the object may have brought another ray to the hit
+        let r_in = rec.incident.unwrap_or(ray);
+        let emitted = Vec3::elemul(
+            rec.weight,
+            rec.mat_ptr.emitted(&r_in, &rec, rec.u, rec.v, &rec.p),
+        );
+        let scattered = rec.mat_ptr.scatter(&r_in, &rec);
+        //the emission of surfaces that reflect specularly is dropped
+        let specular = matches!(&scattered, Some(s_rec) if s_rec.is_specular);
+        if !specular && emitted != Vec3::zero() {
+            let source = if split {
+                light_index(lights, &ray, rec.t).unwrap_or(n_lights)
+            } else {
+                n_lights
+            };
+            add(bounce, source, Vec3::elemul(throughput, emitted));
+        }
+        let s_rec = match scattered {
+            Some(s_rec) => s_rec,
+            None => break,
+        };
+        let attenuation = Vec3::elemul(s_rec.attenuation, rec.weight);
+        if s_rec.is_specular {
+            let mut next = s_rec.specular_ray;
+            next.inherit_cone(&ray, rec.t);
+            throughput = Vec3::elemul(throughput, attenuation);
+            ray = next;
+        } else {
+            let (direction, pdf_val) = sample_direction(lights, &rec.p, s_rec.pdf_ptr);
+            let mut next = Ray::new(rec.p, direction, ray.tm);
+            next.inherit_cone(&ray, rec.t);
+            let scattering = rec.mat_ptr.scattering_value(&r_in, &rec, &next);
+            throughput = Vec3::elemul(throughput, attenuation * scattering / pdf_val);
+            ray = next;
+        }
+    }
+    color
+}
+
+//the entry of lights the ray hits at t, if any
+pub(crate) fn light_index(lights: &HittableList, r: &Ray, t: f64) -> Option<usize> {
+    let eps = 1e-6 * t.max(1.0);
+    lights
+        .objects
+        .iter()
+        .position(|light| match light.hit(r, t - eps, t + eps) {
+            Some(rec) => (rec.t - t).abs() <= eps,
+            None => false,
+        })
+}
+
+//Surface data at the camera ray's first hit for the denoiser and the AOV passes.
+//Albedo is the attenuation of the hit, or the clamped emission of lights, misses
+//report the background and zero for everything else.
+pub fn first_hit_features(r: &Ray, background: &Vec3, world: &HittableList) -> Features {
+    if let Some(mut rec) = world.hit(r, 0.001, INF) {
+        rec.set_footprint(r);
+        let r_in = rec.incident.unwrap_or(*r);
+        let albedo = match rec.mat_ptr.scatter(&r_in, &rec) {
+            Some(s_rec) => Vec3::elemul(s_rec.attenuation, rec.weight),
+            None => {
+                let e = rec.mat_ptr.emitted(&r_in, &rec, rec.u, rec.v, &rec.p);
+                Vec3::new(e.x.min(1.0), e.y.min(1.0), e.z.min(1.0))
+            }
+        };
+        Features {
+            albedo,
+            normal: rec.normal,
+            depth: rec.t * r.dir.length(),
+            position: rec.p,
+            u: rec.u,
+            v: rec.v,
+            material_id: instance_key(rec.mat_ptr.instance_id()),
+            object_id: rec.object_id,
+        }
+    } else {
+        Features::background(*background)
+    }
+}
+
+//folds a material's instance_id into the film's IDs, never 0, which misses have
+fn instance_key(id: usize) -> u32 {
+    let id = id as u64;
+    ((id ^ (id >> 32)) as u32).max(1)
+}
+
+//mixes light and material sampling, scenes without lights only sample the material
+pub fn sample_direction(lights: &HittableList, p: &Vec3, mat_pdf: Arc<dyn PDF>) -> (Vec3, f64) {
+    if lights.objects.is_empty() {
+        let direction = mat_pdf.generate();
+        return (direction, mat_pdf.value(&direction));
+    }
+    let light_ptr = HittablePDF::new(lights, p);
+    let mixture = MixturePDF::new(light_ptr, mat_pdf);
+    let direction = mixture.generate();
+    (direction, mixture.value(&direction))
+}
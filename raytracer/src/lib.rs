@@ -0,0 +1,158 @@
+#![allow(clippy::float_cmp)]
+#![allow(clippy::eq_op)]
+#![allow(clippy::many_single_char_names)]
+#![allow(unused_variables)]
+#![allow(unused_assignments)]
+#![allow(unused_mut)]
+#![allow(clippy::let_and_return)]
+#![allow(clippy::clone_on_copy)]
+#![allow(non_snake_case)]
+#![allow(clippy::redundant_clone)]
+#![allow(clippy::manual_swap)]
+#![allow(clippy::new_without_default)]
+#![allow(clippy::manual_map)]
+#![allow(clippy::too_many_arguments)]
+#![allow(clippy::needless_return)]
+
+pub mod aabb;
+pub mod aov;
+pub mod bdpt;
+pub mod bvh;
+pub mod camera;
+pub mod checkpoint;
+pub mod cornell_box;
+pub mod denoise;
+pub mod emitter;
+pub mod error;
+pub mod film;
+pub mod filter;
+pub mod heightfield;
+pub mod hittable;
+pub mod hittable_list;
+pub mod integrator;
+pub mod material;
+pub mod medium;
+pub mod mlt;
+pub mod onb;
+pub mod pdf;
+pub mod perlin;
+pub mod photon;
+pub mod ray;
+pub mod rectangle;
+pub mod render;
+pub mod scene;
+pub mod scheduler;
+pub mod spectrum;
+pub mod sphere;
+pub mod texture;
+pub mod thin_film;
+pub mod vec3;
+
+pub use aov::Aov;
+pub use camera::Camera;
+pub use checkpoint::Checkpoint;
+pub use denoise::Denoiser;
+pub use error::{RenderError, Result};
+pub use film::Film;
+pub use filter::{Filter, FilterKind};
+pub use hittable::{HitRecord, Hittable};
+pub use hittable_list::HittableList;
+pub use material::{Lambertian, Material};
+pub use pdf::{HittablePDF, MixturePDF, PDF};
+pub use ray::Ray;
+pub use rectangle::XzRect;
+pub use render::{render, render_pass, RenderSettings};
+pub use scene::Scene;
+pub use sphere::Sphere;
+pub use texture::{SolidColor, Texture};
+pub use vec3::Vec3;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const INF: f64 = f64::MAX;
+pub const PI: f64 = std::f64::consts::PI;
+
+pub fn degrees_to_radians(degrees: f64) -> f64 {
+    degrees * PI / 180.0
+}
+
+//Source of the numbers random_0_1 returns in place of the thread's generator,
+//see with_sampler. Metropolis sampling hands out a primary sample vector it
+//mutates between paths.
+pub trait Sampler {
+    fn next_1d(&mut self) -> f64;
+}
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+    static SAMPLER: RefCell<Option<Rc<RefCell<dyn Sampler>>>> = RefCell::new(None);
+}
+
+//runs f with random_0_1 drawing from sampler on this thread
+pub fn with_sampler<R>(sampler: Rc<RefCell<dyn Sampler>>, f: impl FnOnce() -> R) -> R {
+    let _restore = RestoreSampler(SAMPLER.with(|s| s.replace(Some(sampler))));
+    f()
+}
+
+//puts the sampler it holds back in place when dropped, also when f panics
+struct RestoreSampler(Option<Rc<RefCell<dyn Sampler>>>);
+
+impl Drop for RestoreSampler {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        //the thread local is gone if the thread itself is being torn down
+        let _ = SAMPLER.try_with(|s| s.replace(previous));
+    }
+}
+
+//restarts this thread's random sequence, renders reseed it for every tile so a
+//pass comes out the same whichever thread draws it
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+//the next number of the sampler installed on this thread, if there is one
+fn sampled() -> Option<f64> {
+    SAMPLER.with(|s| s.borrow().as_ref().map(|s| s.borrow_mut().next_1d()))
+}
+
+pub fn random_0_1() -> f64 {
+    //return an f64 in [0,1)
+    match sampled() {
+        Some(u) => u,
+        None => RNG.with(|rng| rng.borrow_mut().gen::<f64>()),
+    }
+}
+
+pub fn random_min_max(min: f64, max: f64) -> f64 {
+    //return an f64 in [min,max)
+    match sampled() {
+        Some(u) => min + (max - min) * u,
+        None => RNG.with(|rng| rng.borrow_mut().gen_range(min..max)),
+    }
+}
+
+pub fn random_int(min: i32, max: i32) -> i32 {
+    //return an i32 in[min,max]
+    (random_min_max(min as f64, max as f64 + 1.0) as i32).min(max)
+}
+
+pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
+    if x < min {
+        min
+    } else if x > max {
+        max
+    } else {
+        x
+    }
+}
+
+//a file in the temp directory named per process, test runs at the same time
+//must not share files
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("raytracer_{}_{}", std::process::id(), name))
+}
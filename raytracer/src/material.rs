@@ -1,9 +1,9 @@
 use crate::hittable::HitRecord;
-use crate::pdf::{CosinePDF, PDF};
+use crate::pdf::{CosinePDF, DoubleHenyeyGreensteinPDF, HenyeyGreensteinPDF, SpherePDF, PDF};
 use crate::ray::Ray;
-use crate::texture::{SolidColor, Texture};
+use crate::texture::Texture;
 use crate::vec3::Vec3;
-use crate::{random_0_1, PI};
+use crate::{clamp, random_0_1, PI};
 use num_traits::pow;
 use std::sync::Arc;
 
@@ -12,6 +12,10 @@ pub struct ScatterRecord {
     pub specular_ray: Ray,
     pub pdf_ptr: Arc<dyn PDF>,
     pub is_specular: bool,
+    //n_i / n_t of a specular refraction, 1 for everything else. Radiance keeps
+    //its value across the interface, the importance light paths carry is scaled
+    //by the square of it.
+    pub eta: f64,
 }
 
 impl ScatterRecord {
@@ -21,51 +25,103 @@ impl ScatterRecord {
             specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
             pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
             is_specular: false,
+            eta: 1.0,
         }
     }
 }
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         None
     }
 
+    //density the material samples scattered with, that of pdf_ptr
     fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
         0.0
     }
 
+    //f times cosine towards scattered, over attenuation. Integrators multiply it
+    //by the attenuation and divide by the density they sampled scattered with.
+    //Materials sampling their lobe exactly have it equal to scattering_pdf.
+    fn scattering_value(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.scattering_pdf(r, rec, scattered)
+    }
+
     fn emitted(&self, r: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Vec3) -> Vec3 {
         Vec3::zero()
     }
-}
 
-pub struct Lambertian {
-    pub albedo: Arc<dyn Texture>,
+    //true if scatter() depends on r.lambda, spectral paths then keep only their hero wavelength
+    fn is_wavelength_dependent(&self) -> bool {
+        false
+    }
+
+    //Tells material instances apart, objects sharing an Arc share it. Only
+    //stable while the scene lives, the material ID pass numbers them densely.
+    fn instance_id(&self) -> usize {
+        self as *const Self as *const u8 as usize
+    }
+
+    //true for phase functions, whose hits lie inside a medium and have no normal
+    //to take cosines with
+    fn is_volumetric(&self) -> bool {
+        false
+    }
 }
 
-impl Lambertian {
-    pub fn new_with_vec(a: Vec3) -> Self {
-        Self {
-            albedo: Arc::new(SolidColor::new_with_vec(a)),
-        }
+//one material shared by many objects
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        (**self).scatter(r, rec)
+    }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        (**self).scattering_pdf(r, rec, scattered)
+    }
+
+    fn scattering_value(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        (**self).scattering_value(r, rec, scattered)
     }
 
-    pub fn new_with_arc(a: Arc<dyn Texture>) -> Self {
+    fn emitted(&self, r: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        (**self).emitted(r, rec, u, v, p)
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        (**self).is_wavelength_dependent()
+    }
+
+    fn instance_id(&self) -> usize {
+        (**self).instance_id()
+    }
+
+    fn is_volumetric(&self) -> bool {
+        (**self).is_volumetric()
+    }
+}
+
+#[derive(Clone)]
+pub struct Lambertian<T: Texture> {
+    pub albedo: T,
+}
+
+impl<T: Texture> Lambertian<T> {
+    pub fn new(a: T) -> Self {
         Self { albedo: a }
     }
 }
 
-impl Material for Lambertian {
+impl<T: Texture> Material for Lambertian<T> {
     fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        // let mut uvw = ONB::new();
-        // uvw.build_from_w(rec.normal);
-        // let direction = uvw.local_with_vec(Vec3::random_cosine_direction());
         let s_rec = ScatterRecord {
             // specular_ray: Ray::new(rec.p, direction, r.tm),
             specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
-            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            attenuation: self
+                .albedo
+                .filtered_value(rec.u, rec.v, &rec.p, rec.footprint),
             pdf_ptr: Arc::new(CosinePDF::new(&rec.normal)),
             is_specular: false,
+            eta: 1.0,
         };
         Some(s_rec)
     }
@@ -80,6 +136,7 @@ impl Material for Lambertian {
     }
 }
 
+#[derive(Clone)]
 pub struct Metal {
     pub albedo: Vec3,
     pub fuzz: f64,
@@ -106,19 +163,84 @@ impl Material for Metal {
             attenuation: self.albedo,
             is_specular: true,
             pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
+            eta: 1.0,
         };
         Some(s_rec)
     }
 }
 
+#[derive(Clone)]
+pub enum Dispersion {
+    None,
+    //n = a + b / lambda^2, lambda in micrometers
+    Cauchy(f64, f64),
+    //n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i), lambda in micrometers
+    Sellmeier([f64; 3], [f64; 3]),
+}
+
+#[derive(Clone)]
 pub struct Dielectric {
     pub ir: f64,
+    pub dispersion: Dispersion,
 }
 
 impl Dielectric {
+    //sodium d line, used for dispersive glasses outside of spectral mode
+    const LAMBDA_D: f64 = 587.6;
+
     pub fn new(index_of_refraction: f64) -> Self {
         Self {
             ir: index_of_refraction,
+            dispersion: Dispersion::None,
+        }
+    }
+
+    pub fn new_with_cauchy(a: f64, b: f64) -> Self {
+        let mut d = Self {
+            ir: 1.0,
+            dispersion: Dispersion::Cauchy(a, b),
+        };
+        d.ir = d.ior(Self::LAMBDA_D);
+        d
+    }
+
+    pub fn new_with_sellmeier(b: [f64; 3], c: [f64; 3]) -> Self {
+        let mut d = Self {
+            ir: 1.0,
+            dispersion: Dispersion::Sellmeier(b, c),
+        };
+        d.ir = d.ior(Self::LAMBDA_D);
+        d
+    }
+
+    //Schott N-BK7 crown glass
+    pub fn bk7() -> Self {
+        Self::new_with_sellmeier(
+            [1.03961212, 0.231792344, 1.01046945],
+            [0.00600069867, 0.0200179144, 103.560653],
+        )
+    }
+
+    pub fn diamond() -> Self {
+        Self::new_with_cauchy(2.385, 0.0117)
+    }
+
+    pub fn ior(&self, lambda: f64) -> f64 {
+        if lambda <= 0.0 {
+            return self.ir;
+        }
+        let l = lambda * 1e-3;
+        match &self.dispersion {
+            Dispersion::None => self.ir,
+            Dispersion::Cauchy(a, b) => a + b / (l * l),
+            Dispersion::Sellmeier(b, c) => {
+                let l2 = l * l;
+                let mut n2 = 1.0;
+                for i in 0..3 {
+                    n2 += b[i] * l2 / (l2 - c[i]);
+                }
+                n2.sqrt()
+            }
         }
     }
 
@@ -131,11 +253,8 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        let ir = self.ior(r.lambda);
+        let refraction_ratio = if rec.front_face { 1.0 / ir } else { ir };
         let unit_direction = r.dir.unit();
         let mut cos_theta = -unit_direction * rec.normal;
         if cos_theta > 1.0 {
@@ -144,75 +263,670 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let flag = refraction_ratio * sin_theta > 1.0
             || Self::reflectance(cos_theta, refraction_ratio) > random_0_1();
-        let direction = if flag {
-            Vec3::reflect(unit_direction, rec.normal)
+        let (direction, eta) = if flag {
+            (Vec3::reflect(unit_direction, rec.normal), 1.0)
         } else {
-            Vec3::refract(unit_direction, rec.normal, refraction_ratio)
+            (
+                Vec3::refract(unit_direction, rec.normal, refraction_ratio),
+                refraction_ratio,
+            )
         };
         let s_rec = ScatterRecord {
             attenuation: Vec3::ones(),
             specular_ray: Ray::new(rec.p, direction, r.tm),
             pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
             is_specular: true,
+            eta,
         };
         Some(s_rec)
     }
+    fn is_wavelength_dependent(&self) -> bool {
+        !matches!(self.dispersion, Dispersion::None)
+    }
 }
 
-pub struct DiffuseLight {
-    emit: Arc<dyn Texture>,
+#[derive(Clone)]
+pub struct DiffuseLight<T: Texture> {
+    emit: T,
 }
 
-impl DiffuseLight {
-    pub fn new_with_arc(a: Arc<dyn Texture>) -> Self {
+impl<T: Texture> DiffuseLight<T> {
+    pub fn new(a: T) -> Self {
         Self { emit: a }
     }
-
-    pub fn new_with_vec(c: Vec3) -> Self {
-        Self {
-            emit: Arc::new(SolidColor::new_with_vec(c)),
-        }
-    }
 }
 
-impl Material for DiffuseLight {
+impl<T: Texture> Material for DiffuseLight<T> {
     fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         None
     }
 
     fn emitted(&self, r: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Vec3) -> Vec3 {
         if rec.front_face {
-            self.emit.value(u, v, p)
+            self.emit.filtered_value(u, v, p, rec.footprint)
         } else {
             Vec3::zero()
         }
     }
 }
 
-pub struct Isotropic {
-    pub albedo: Arc<dyn Texture>,
+#[derive(Clone)]
+pub struct Isotropic<T: Texture> {
+    pub albedo: T,
+}
+
+impl<T: Texture> Isotropic<T> {
+    pub fn new(a: T) -> Self {
+        Self { albedo: a }
+    }
+}
+
+impl<T: Texture> Material for Isotropic<T> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let s_rec = ScatterRecord {
+            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
+            pdf_ptr: Arc::new(SpherePDF::new()),
+            is_specular: false,
+            eta: 1.0,
+        };
+        Some(s_rec)
+    }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct HenyeyGreenstein<T: Texture> {
+    pub albedo: T,
+    pub g: f64,
 }
 
-impl Isotropic {
-    pub fn new_with_col(c: Vec3) -> Self {
+impl<T: Texture> HenyeyGreenstein<T> {
+    pub fn new(a: T, g: f64) -> Self {
         Self {
-            albedo: Arc::new(SolidColor::new_with_vec(c)),
+            albedo: a,
+            g: clamp(g, -0.999, 0.999),
         }
     }
+}
 
-    pub fn new_with_arc(a: Arc<dyn Texture>) -> Self {
-        Self { albedo: a }
+impl<T: Texture> Material for HenyeyGreenstein<T> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let s_rec = ScatterRecord {
+            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
+            pdf_ptr: Arc::new(HenyeyGreensteinPDF::new(&r.dir, self.g)),
+            is_specular: false,
+            eta: 1.0,
+        };
+        Some(s_rec)
+    }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        HenyeyGreensteinPDF::phase(r.dir.unit() * scattered.dir.unit(), self.g)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct DoubleHenyeyGreenstein<T: Texture> {
+    pub albedo: T,
+    pub g1: f64,
+    pub g2: f64,
+    pub weight: f64,
+}
+
+impl<T: Texture> DoubleHenyeyGreenstein<T> {
+    pub fn new(a: T, g1: f64, g2: f64, weight: f64) -> Self {
+        Self {
+            albedo: a,
+            g1: clamp(g1, -0.999, 0.999),
+            g2: clamp(g2, -0.999, 0.999),
+            weight: clamp(weight, 0.0, 1.0),
+        }
     }
 }
 
-impl Material for Isotropic {
+impl<T: Texture> Material for DoubleHenyeyGreenstein<T> {
     fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let s_rec = ScatterRecord {
             attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
-            specular_ray: Ray::new(rec.p, Vec3::random_in_unit_sphere(), r.tm),
-            pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
-            is_specular: true,
+            specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
+            pdf_ptr: Arc::new(DoubleHenyeyGreensteinPDF::new(
+                &r.dir,
+                self.g1,
+                self.g2,
+                self.weight,
+            )),
+            is_specular: false,
+            eta: 1.0,
+        };
+        Some(s_rec)
+    }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = r.dir.unit() * scattered.dir.unit();
+        self.weight * HenyeyGreensteinPDF::phase(cos_theta, self.g1)
+            + (1.0 - self.weight) * HenyeyGreensteinPDF::phase(cos_theta, self.g2)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+//Glossy dielectric coat over any base material, e.g. car paint or varnished wood.
+//The coat reflects with its Fresnel probability, otherwise light passes to the
+//base and is absorbed on the way in and out. `tint` is the colour a single normal
+//pass through a coat of unit thickness leaves, light lost to the exit Fresnel is
+//not returned, so the layer never adds energy.
+#[derive(Clone)]
+pub struct Clearcoat<M: Material> {
+    pub base: M,
+    pub ir: f64,
+    pub fuzz: f64,
+    pub absorption: Vec3,
+}
+
+impl<M: Material> Clearcoat<M> {
+    pub fn new(base: M, ir: f64, thickness: f64, tint: Vec3) -> Self {
+        let sigma = |t: f64| -clamp(t, 1e-6, 1.0).ln() * thickness;
+        Self {
+            base,
+            ir,
+            fuzz: 0.0,
+            absorption: Vec3::new(sigma(tint.x), sigma(tint.y), sigma(tint.z)),
+        }
+    }
+
+    pub fn new_with_fuzz(base: M, ir: f64, thickness: f64, tint: Vec3, f: f64) -> Self {
+        let mut coat = Self::new(base, ir, thickness, tint);
+        coat.fuzz = if f < 1.0 { f } else { 1.0 };
+        coat
+    }
+
+    //Fresnel reflectance from outside and the transmittance of one pass through the
+    //coat, for a direction making cos_theta with the normal
+    fn interface(&self, cos_theta: f64) -> (f64, Vec3) {
+        let cos_theta = clamp(cos_theta, 1e-4, 1.0);
+        let fresnel = Dielectric::reflectance(cos_theta, 1.0 / self.ir);
+        let sin2_t = (1.0 - cos_theta * cos_theta) / (self.ir * self.ir);
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let tr = Vec3::new(
+            (-self.absorption.x / cos_t).exp(),
+            (-self.absorption.y / cos_t).exp(),
+            (-self.absorption.z / cos_t).exp(),
+        );
+        (fresnel, tr)
+    }
+}
+
+impl<M: Material> Material for Clearcoat<M> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let unit_direction = r.dir.unit();
+        let (fresnel, tr_in) = self.interface(-unit_direction * rec.normal);
+        if random_0_1() < fresnel {
+            let reflected = Vec3::reflect(unit_direction, rec.normal);
+            let s_rec = ScatterRecord {
+                attenuation: Vec3::ones(),
+                specular_ray: Ray::new(
+                    rec.p,
+                    reflected + Vec3::random_in_unit_sphere() * self.fuzz,
+                    r.tm,
+                ),
+                pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
+                is_specular: true,
+                eta: 1.0,
+            };
+            return Some(s_rec);
+        }
+        let mut s_rec = self.base.scatter(r, rec)?;
+        s_rec.attenuation = Vec3::elemul(s_rec.attenuation, tr_in);
+        //the way out is known only for specular bases, diffuse ones leave along
+        //the normal for the tint and get the exit Fresnel in scattering_value
+        let cos_out = if s_rec.is_specular {
+            s_rec.specular_ray.dir.unit() * rec.normal
+        } else {
+            1.0
+        };
+        let (fresnel_out, tr_out) = self.interface(cos_out);
+        s_rec.attenuation = Vec3::elemul(s_rec.attenuation, tr_out);
+        if s_rec.is_specular {
+            s_rec.attenuation *= 1.0 - fresnel_out;
+        }
+        Some(s_rec)
+    }
+
+    //the base samples the light the coat lets through
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.scattering_pdf(r, rec, scattered)
+    }
+
+    //the base's lobe less what the exit Fresnel reflects back in
+    fn scattering_value(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (fresnel_out, _) = self.interface(scattered.dir.unit() * rec.normal);
+        self.base.scattering_value(r, rec, scattered) * (1.0 - fresnel_out)
+    }
+
+    fn emitted(&self, r: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.base.emitted(r, rec, u, v, p)
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        self.base.is_wavelength_dependent()
+    }
+}
+
+//Blends two materials by a mask texture, 0 selects `a` and 1 selects `b`. Each hit
+//scatters off one of them with the mask as probability, picked by hashing the hit
+//so scatter, scattering_pdf and scattering_value always agree on the lobe. Emission is blended exactly.
+#[derive(Clone)]
+pub struct MixMaterial<A: Material, B: Material, T: Texture> {
+    pub a: A,
+    pub b: B,
+    pub mask: T,
+}
+
+impl<A: Material, B: Material, T: Texture> MixMaterial<A, B, T> {
+    pub fn new(a: A, b: B, mask: T) -> Self {
+        Self { a, b, mask }
+    }
+
+    fn weight(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        let m = self.mask.value(u, v, p);
+        clamp((m.x + m.y + m.z) / 3.0, 0.0, 1.0)
+    }
+
+    fn picks_b(&self, r: &Ray, rec: &HitRecord) -> bool {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        if w <= 0.0 {
+            return false;
+        }
+        if w >= 1.0 {
+            return true;
+        }
+        let mut h: u64 = 0x9E3779B97F4A7C15;
+        for x in [rec.p.x, rec.p.y, rec.p.z, r.dir.x, r.dir.y, r.dir.z].iter() {
+            h ^= x.to_bits();
+            h = h.wrapping_mul(0xBF58476D1CE4E5B9);
+            h ^= h >> 31;
+        }
+        ((h >> 11) as f64 / (1u64 << 53) as f64) < w
+    }
+}
+
+impl<A: Material, B: Material, T: Texture> Material for MixMaterial<A, B, T> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        if self.picks_b(r, rec) {
+            self.b.scatter(r, rec)
+        } else {
+            self.a.scatter(r, rec)
+        }
+    }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.picks_b(r, rec) {
+            self.b.scattering_pdf(r, rec, scattered)
+        } else {
+            self.a.scattering_pdf(r, rec, scattered)
+        }
+    }
+
+    fn scattering_value(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.picks_b(r, rec) {
+            self.b.scattering_value(r, rec, scattered)
+        } else {
+            self.a.scattering_value(r, rec, scattered)
+        }
+    }
+
+    fn emitted(&self, r: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let w = self.weight(u, v, p);
+        self.a.emitted(r, rec, u, v, p) * (1.0 - w) + self.b.emitted(r, rec, u, v, p) * w
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        self.a.is_wavelength_dependent() || self.b.is_wavelength_dependent()
+    }
+}
+
+//Oren-Nayar rough diffuse (qualitative model), sigma is the slope deviation in
+//radians read from the mean of the sigma texture. Cosine sampled like Lambertian.
+#[derive(Clone)]
+pub struct OrenNayar<T: Texture, S: Texture> {
+    pub albedo: T,
+    pub sigma: S,
+}
+
+impl<T: Texture, S: Texture> OrenNayar<T, S> {
+    pub fn new(a: T, sigma: S) -> Self {
+        Self { albedo: a, sigma }
+    }
+}
+
+impl<T: Texture, S: Texture> Material for OrenNayar<T, S> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let s_rec = ScatterRecord {
+            specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
+            attenuation: self
+                .albedo
+                .filtered_value(rec.u, rec.v, &rec.p, rec.footprint),
+            pdf_ptr: Arc::new(CosinePDF::new(&rec.normal)),
+            is_specular: false,
+            eta: 1.0,
+        };
+        Some(s_rec)
+    }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = rec.normal * scattered.dir.unit();
+        if cosine < 0.0 {
+            0.0
+        } else {
+            cosine / PI
+        }
+    }
+
+    fn scattering_value(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let wi = -r.dir.unit();
+        let wo = scattered.dir.unit();
+        let cos_i = clamp(rec.normal * wi, 0.0, 1.0);
+        let cos_o = rec.normal * wo;
+        if cos_o <= 0.0 {
+            return 0.0;
+        }
+        let s = self.sigma.value(rec.u, rec.v, &rec.p);
+        let sigma2 = pow((s.x + s.y + s.z) / 3.0, 2);
+        let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+        let sin_i = (1.0 - cos_i * cos_i).sqrt();
+        let sin_o = (1.0 - cos_o * cos_o).sqrt();
+        //cosine of the azimuth difference, from the tangent plane projections
+        let ti = wi - rec.normal * cos_i;
+        let to = wo - rec.normal * cos_o;
+        let cos_phi = if ti.near_zero() || to.near_zero() {
+            0.0
+        } else {
+            (ti.unit() * to.unit()).max(0.0)
+        };
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i.max(1e-4))
+        } else {
+            (sin_i, sin_o / cos_o)
+        };
+        cos_o / PI * (a + b * cos_phi * sin_alpha * tan_beta)
+    }
+}
+
+//Charlie sheen lobe for cloth and velvet (Estevez and Kulla 2017) with the
+//Neubelt-Pettineo visibility term. Mostly grazing light, so it is usually mixed
+//over a diffuse base with MixMaterial.
+#[derive(Clone)]
+pub struct Sheen<T: Texture> {
+    pub color: T,
+    pub roughness: f64,
+}
+
+impl<T: Texture> Sheen<T> {
+    pub fn new(c: T, roughness: f64) -> Self {
+        Self {
+            color: c,
+            roughness: clamp(roughness, 0.01, 1.0),
+        }
+    }
+}
+
+impl<T: Texture> Material for Sheen<T> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let s_rec = ScatterRecord {
+            specular_ray: Ray::new(Vec3::zero(), Vec3::zero(), 0.0),
+            attenuation: self
+                .color
+                .filtered_value(rec.u, rec.v, &rec.p, rec.footprint),
+            pdf_ptr: Arc::new(CosinePDF::new(&rec.normal)),
+            is_specular: false,
+            eta: 1.0,
         };
         Some(s_rec)
     }
+
+    fn scattering_pdf(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = rec.normal * scattered.dir.unit();
+        if cosine < 0.0 {
+            0.0
+        } else {
+            cosine / PI
+        }
+    }
+
+    fn scattering_value(&self, r: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let wi = -r.dir.unit();
+        let wo = scattered.dir.unit();
+        let cos_i = clamp(rec.normal * wi, 0.0, 1.0);
+        let cos_o = rec.normal * wo;
+        if cos_o <= 0.0 {
+            return 0.0;
+        }
+        let cos_h = clamp(rec.normal * (wi + wo).unit(), 0.0, 1.0);
+        let sin_h = (1.0 - cos_h * cos_h).sqrt();
+        let inv_alpha = 1.0 / self.roughness;
+        let d = (2.0 + inv_alpha) * sin_h.powf(inv_alpha) / (2.0 * PI);
+        let v = 1.0 / (4.0 * (cos_i + cos_o - cos_i * cos_o));
+        d * v * cos_o
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::SolidColor;
+
+    //a hit on the plane z = 0 facing +z by a ray making cos_theta with the normal
+    fn hit_from(mat: &dyn Material, cos_theta: f64) -> (Ray, HitRecord<'_>) {
+        let mut rec = HitRecord::new(mat);
+        rec.normal = Vec3::new(0.0, 0.0, 1.0);
+        rec.front_face = true;
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let r = Ray::new(
+            Vec3::new(-sin_theta, 0.0, cos_theta),
+            Vec3::new(sin_theta, 0.0, -cos_theta),
+            0.0,
+        );
+        (r, rec)
+    }
+
+    //the share of light from r the material scatters, estimated from n of its
+    //samples weighed the way the integrators weigh them
+    fn albedo(mat: &dyn Material, r: &Ray, rec: &HitRecord, n: usize) -> Vec3 {
+        let mut sum = Vec3::zero();
+        for _ in 0..n {
+            let s_rec = match mat.scatter(r, rec) {
+                Some(s_rec) => s_rec,
+                None => continue,
+            };
+            if s_rec.is_specular {
+                sum += s_rec.attenuation;
+                continue;
+            }
+            let scattered = Ray::new(rec.p, s_rec.pdf_ptr.generate(), r.tm);
+            let pdf = s_rec.pdf_ptr.value(&scattered.dir);
+            if pdf > 0.0 {
+                sum += s_rec.attenuation * mat.scattering_value(r, rec, &scattered) / pdf;
+            }
+        }
+        sum / n as f64
+    }
+
+    //scatter samples directions with the density scattering_pdf reports, and
+    //that density integrates to one over the sphere
+    fn assert_pdf_matches_scatter(mat: &dyn Material, r: &Ray, rec: &HitRecord) {
+        for _ in 0..1000 {
+            let s_rec = mat.scatter(r, rec).unwrap();
+            if s_rec.is_specular {
+                continue;
+            }
+            let scattered = Ray::new(rec.p, s_rec.pdf_ptr.generate(), r.tm);
+            let pdf = mat.scattering_pdf(r, rec, &scattered);
+            assert!((s_rec.pdf_ptr.value(&scattered.dir) - pdf).abs() < 1e-9);
+            let value = mat.scattering_value(r, rec, &scattered);
+            assert!(value.is_finite() && value >= 0.0, "{}", value);
+        }
+        let n = 100_000;
+        let integral: f64 = (0..n)
+            .map(|_| {
+                let scattered = Ray::new(rec.p, Vec3::random_unit_vector(), r.tm);
+                mat.scattering_pdf(r, rec, &scattered) * 4.0 * PI
+            })
+            .sum::<f64>()
+            / n as f64;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+
+    fn white() -> Lambertian<SolidColor> {
+        Lambertian::new(SolidColor::new_with_vec(Vec3::ones()))
+    }
+
+    #[test]
+    fn test_clearcoat_conserves_energy() {
+        //a clear coat over a white base loses only what the exit Fresnel keeps in
+        let coat = Clearcoat::new(white(), 1.5, 1.0, Vec3::ones());
+        for cos_theta in [1.0, 0.5, 0.1].iter() {
+            let (r, rec) = hit_from(&coat, *cos_theta);
+            let a = albedo(&coat, &r, &rec, 50_000);
+            assert!(a.x > 0.85 && a.x <= 1.0, "{:?}", a);
+            assert!(a.x == a.y && a.y == a.z, "{:?}", a);
+        }
+    }
+
+    #[test]
+    fn test_clearcoat_pdf_matches_scatter() {
+        let coat = Clearcoat::new(white(), 1.5, 1.0, Vec3::new(0.8, 0.5, 0.2));
+        for cos_theta in [1.0, 0.3].iter() {
+            let (r, rec) = hit_from(&coat, *cos_theta);
+            assert_pdf_matches_scatter(&coat, &r, &rec);
+            //the coat only takes light away from the base's lobe
+            for _ in 0..1000 {
+                let scattered = Ray::new(rec.p, Vec3::random_unit_vector(), r.tm);
+                let value = coat.scattering_value(&r, &rec, &scattered);
+                assert!(value <= white().scattering_value(&r, &rec, &scattered));
+            }
+        }
+    }
+
+    #[test]
+    fn test_oren_nayar_conserves_energy() {
+        for cos_theta in [1.0, 0.5, 0.1].iter() {
+            for sigma in [0.0, 0.3, 1.0].iter() {
+                let sigma_texture = SolidColor::new_with_vec(Vec3::ones() * *sigma);
+                let rough = OrenNayar::new(SolidColor::new_with_vec(Vec3::ones()), sigma_texture);
+                let (r, rec) = hit_from(&rough, *cos_theta);
+                let a = albedo(&rough, &r, &rec, 20_000).x;
+                //smooth it is Lambertian, rougher it only loses light
+                if *sigma == 0.0 {
+                    assert!((a - 1.0).abs() < 1e-9, "{}", a);
+                } else {
+                    assert!(a > 0.5 && a <= 1.0, "{} {} {}", cos_theta, sigma, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sheen_conserves_energy() {
+        for cos_theta in [1.0, 0.5, 0.1].iter() {
+            for roughness in [0.3, 1.0].iter() {
+                let sheen = Sheen::new(SolidColor::new_with_vec(Vec3::ones()), *roughness);
+                let (r, rec) = hit_from(&sheen, *cos_theta);
+                let a = albedo(&sheen, &r, &rec, 20_000).x;
+                assert!(a > 0.0 && a <= 1.0, "{} {} {}", cos_theta, roughness, a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_oren_nayar_and_sheen_pdf_match_scatter() {
+        let rough = OrenNayar::new(
+            SolidColor::new_with_vec(Vec3::ones()),
+            SolidColor::new_with_col(0.5, 0.5, 0.5),
+        );
+        let sheen = Sheen::new(SolidColor::new_with_vec(Vec3::ones()), 0.5);
+        for cos_theta in [1.0, 0.3].iter() {
+            let (r, rec) = hit_from(&rough, *cos_theta);
+            assert_pdf_matches_scatter(&rough, &r, &rec);
+            let (r, rec) = hit_from(&sheen, *cos_theta);
+            assert_pdf_matches_scatter(&sheen, &r, &rec);
+        }
+    }
+
+    #[test]
+    fn test_mix_material_picks_lobes_by_mask() {
+        let red = || Lambertian::new(SolidColor::new_with_col(1.0, 0.0, 0.0));
+        let blue = || Sheen::new(SolidColor::new_with_col(0.0, 0.0, 1.0), 0.5);
+        let mix =
+            |w: f64| MixMaterial::new(red(), blue(), SolidColor::new_with_vec(Vec3::ones() * w));
+        //at the ends of the mask the mix is one of its materials
+        for (w, expected) in [
+            (0.0, Vec3::new(1.0, 0.0, 0.0)),
+            (1.0, Vec3::new(0.0, 0.0, 1.0)),
+        ]
+        .iter()
+        {
+            let mix = mix(*w);
+            for _ in 0..100 {
+                let (r, rec) = hit_from(&mix, random_0_1());
+                let scattered = Ray::new(rec.p, Vec3::random_unit_vector(), r.tm);
+                let (pdf, value) = if *w == 0.0 {
+                    (
+                        red().scattering_pdf(&r, &rec, &scattered),
+                        red().scattering_value(&r, &rec, &scattered),
+                    )
+                } else {
+                    (
+                        blue().scattering_pdf(&r, &rec, &scattered),
+                        blue().scattering_value(&r, &rec, &scattered),
+                    )
+                };
+                assert_eq!(mix.scatter(&r, &rec).unwrap().attenuation, *expected);
+                assert_eq!(mix.scattering_pdf(&r, &rec, &scattered), pdf);
+                assert_eq!(mix.scattering_value(&r, &rec, &scattered), value);
+            }
+        }
+        //halfway every hit weighs its scattered ray by the lobe scatter picked, and
+        //the hits average the two materials
+        let half = mix(0.5);
+        let (r, mut rec) = hit_from(&half, 0.5);
+        let n = 50_000;
+        let mut sum = Vec3::zero();
+        for _ in 0..n {
+            rec.p = Vec3::new(random_0_1(), random_0_1(), 0.0);
+            let s_rec = half.scatter(&r, &rec).unwrap();
+            let scattered = Ray::new(rec.p, s_rec.pdf_ptr.generate(), r.tm);
+            let value = half.scattering_value(&r, &rec, &scattered);
+            if s_rec.attenuation.x > 0.0 {
+                assert_eq!(value, red().scattering_value(&r, &rec, &scattered));
+            } else {
+                assert_eq!(value, blue().scattering_value(&r, &rec, &scattered));
+            }
+            sum += s_rec.attenuation * value / s_rec.pdf_ptr.value(&scattered.dir);
+        }
+        let mixed = sum / n as f64;
+        let sheen = albedo(&blue(), &r, &rec, n).z;
+        assert!((mixed.x - 0.5).abs() < 0.02, "{:?}", mixed);
+        assert!(
+            (mixed.z - 0.5 * sheen).abs() < 0.02,
+            "{:?} {}",
+            mixed,
+            sheen
+        );
+    }
 }
@@ -0,0 +1,838 @@
+use crate::aabb::AABB;
+use crate::error::{RenderError, Result};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Material, ScatterRecord};
+use crate::pdf::{HenyeyGreensteinPDF, PDF};
+use crate::perlin::Perlin;
+use crate::ray::Ray;
+use crate::spectrum::blackbody_color;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use crate::{clamp, random_0_1, INF};
+use std::fs;
+use std::sync::Arc;
+
+pub trait DensityField: Sync + Send {
+    fn value(&self, p: &Vec3) -> f64;
+
+    //an upper bound of value() inside the medium, used as the tracking majorant
+    fn majorant(&self) -> f64;
+}
+
+#[derive(Clone)]
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,
+    density: f64,
+}
+
+impl NoiseDensity {
+    pub fn new(sc: f64, d: f64) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale: sc,
+            density: d,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn value(&self, p: &Vec3) -> f64 {
+        let fbm = self.noise.turb(&(*p * self.scale), 7);
+        self.density * fbm.min(1.0)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.density
+    }
+}
+
+#[derive(Clone)]
+pub struct TextureDensity<T: Texture> {
+    texture: T,
+    scale: f64,
+}
+
+impl<T: Texture> TextureDensity<T> {
+    pub fn new(t: T, sc: f64) -> Self {
+        Self {
+            texture: t,
+            scale: sc,
+        }
+    }
+}
+
+impl<T: Texture> DensityField for TextureDensity<T> {
+    //the texture is expected to stay in [0, 1] per channel
+    fn value(&self, p: &Vec3) -> f64 {
+        let c = self.texture.value(0.0, 0.0, p);
+        let v = (c.x + c.y + c.z) / 3.0;
+        self.scale * clamp(v, 0.0, 1.0)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.scale
+    }
+}
+
+#[derive(Clone)]
+pub struct GridDensity {
+    data: Arc<Vec<f32>>,
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    minimum: Vec3,
+    maximum: Vec3,
+    scale: f64,
+    max_value: f64,
+}
+
+impl GridDensity {
+    //data[(k * ny + j) * nx + i], the grid spans [minimum, maximum]
+    pub fn new(
+        data: Vec<f32>,
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        minimum: Vec3,
+        maximum: Vec3,
+        sc: f64,
+    ) -> Result<Self> {
+        if nx == 0 || ny == 0 || nz == 0 || data.len() != nx * ny * nz {
+            return Err(RenderError::invalid_data(
+                "voxel grid",
+                "number of values does not match the grid size",
+            ));
+        }
+        let max_value = data.iter().cloned().fold(0.0_f32, f32::max) as f64;
+        Ok(Self {
+            data: Arc::new(data),
+            nx,
+            ny,
+            nz,
+            minimum,
+            maximum,
+            scale: sc,
+            max_value,
+        })
+    }
+
+    //raw little-endian file: nx, ny, nz as u32 followed by nx * ny * nz f32 values
+    pub fn new_with_file(filename: &str, minimum: Vec3, maximum: Vec3, sc: f64) -> Result<Self> {
+        let bytes = fs::read(filename).map_err(|e| RenderError::io(filename, e))?;
+        if bytes.len() < 12 {
+            return Err(RenderError::invalid_data(
+                filename,
+                "missing voxel grid header",
+            ));
+        }
+        let read_u32 = |i: usize| {
+            u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]) as usize
+        };
+        let (nx, ny, nz) = (read_u32(0), read_u32(4), read_u32(8));
+        let expected = nx
+            .checked_mul(ny)
+            .and_then(|n| n.checked_mul(nz))
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|n| n.checked_add(12));
+        if nx == 0 || ny == 0 || nz == 0 || expected != Some(bytes.len()) {
+            return Err(RenderError::invalid_data(
+                filename,
+                "voxel grid size does not match its header",
+            ));
+        }
+        let mut data = Vec::with_capacity(nx * ny * nz);
+        for n in 0..nx * ny * nz {
+            let i = 12 + 4 * n;
+            data.push(f32::from_le_bytes([
+                bytes[i],
+                bytes[i + 1],
+                bytes[i + 2],
+                bytes[i + 3],
+            ]));
+        }
+        Self::new(data, nx, ny, nz, minimum, maximum, sc)
+    }
+
+    fn voxel(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.data[(k * self.ny + j) * self.nx + i] as f64
+    }
+}
+
+impl DensityField for GridDensity {
+    fn value(&self, p: &Vec3) -> f64 {
+        let extent = self.maximum - self.minimum;
+        //voxel centers sit on the grid points, trilinear in between
+        let gx = (p.x - self.minimum.x) / extent.x * (self.nx - 1) as f64;
+        let gy = (p.y - self.minimum.y) / extent.y * (self.ny - 1) as f64;
+        let gz = (p.z - self.minimum.z) / extent.z * (self.nz - 1) as f64;
+        if gx < 0.0
+            || gy < 0.0
+            || gz < 0.0
+            || gx > (self.nx - 1) as f64
+            || gy > (self.ny - 1) as f64
+            || gz > (self.nz - 1) as f64
+        {
+            return 0.0;
+        }
+        let i = (gx.floor() as usize).min(self.nx.saturating_sub(2));
+        let j = (gy.floor() as usize).min(self.ny.saturating_sub(2));
+        let k = (gz.floor() as usize).min(self.nz.saturating_sub(2));
+        let (fx, fy, fz) = (gx - i as f64, gy - j as f64, gz - k as f64);
+        let i1 = (i + 1).min(self.nx - 1);
+        let j1 = (j + 1).min(self.ny - 1);
+        let k1 = (k + 1).min(self.nz - 1);
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let c00 = lerp(self.voxel(i, j, k), self.voxel(i1, j, k), fx);
+        let c10 = lerp(self.voxel(i, j1, k), self.voxel(i1, j1, k), fx);
+        let c01 = lerp(self.voxel(i, j, k1), self.voxel(i1, j, k1), fx);
+        let c11 = lerp(self.voxel(i, j1, k1), self.voxel(i1, j1, k1), fx);
+        let c0 = lerp(c00, c10, fy);
+        let c1 = lerp(c01, c11, fy);
+        self.scale * lerp(c0, c1, fz)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.scale * self.max_value
+    }
+}
+
+//Walks every boundary crossing along the ray and yields the parts of
+//[t_min, t_max] that lie inside. Inside/outside is decided by crossing parity
+//from -INF, so non-convex boundaries and ray origins inside the medium work.
+pub struct InsideSegments<'a, B: Hittable> {
+    boundary: &'a B,
+    r: &'a Ray,
+    t_min: f64,
+    t_max: f64,
+    last: f64,
+    inside: bool,
+    done: bool,
+}
+
+impl<'a, B: Hittable> InsideSegments<'a, B> {
+    pub fn new(boundary: &'a B, r: &'a Ray, t_min: f64, t_max: f64) -> Self {
+        Self {
+            boundary,
+            r,
+            t_min,
+            t_max,
+            last: -INF,
+            inside: false,
+            done: t_min >= t_max,
+        }
+    }
+}
+
+impl<'a, B: Hittable> Iterator for InsideSegments<'a, B> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<(f64, f64)> {
+        while !self.done {
+            let search_from = if self.last == -INF {
+                -INF
+            } else {
+                self.last + 0.0001
+            };
+            let (crossing, was_inside) = match self.boundary.hit(self.r, search_from, INF) {
+                Some(rec) if !rec.t.is_nan() => (rec.t, self.inside),
+                _ => {
+                    self.done = true;
+                    (INF, self.inside)
+                }
+            };
+            let start = self.last.max(self.t_min);
+            let end = crossing.min(self.t_max);
+            self.last = crossing;
+            self.inside = !self.inside;
+            if crossing >= self.t_max {
+                self.done = true;
+            }
+            if was_inside && start < end {
+                return Some((start, end));
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone)]
+pub struct HeterogeneousMedium<B: Hittable, D: DensityField, P: Material> {
+    pub boundary: B,
+    pub density: D,
+    pub phase_function: P,
+}
+
+impl<B: Hittable, D: DensityField, P: Material> HeterogeneousMedium<B, D, P> {
+    pub fn new(b: B, d: D, p: P) -> Self {
+        Self {
+            boundary: b,
+            density: d,
+            phase_function: p,
+        }
+    }
+
+    //ratio tracking estimate of the transmittance between t_min and t_max
+    pub fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let majorant = self.density.majorant();
+        if majorant <= 0.0 {
+            return 1.0;
+        }
+        let ray_length = r.dir.length();
+        let mut tr = 1.0;
+        for (t0, t1) in InsideSegments::new(&self.boundary, r, t_min, t_max) {
+            let mut t = t0;
+            loop {
+                t -= (1.0 - random_0_1()).ln() / majorant / ray_length;
+                if t >= t1 {
+                    break;
+                }
+                tr *= 1.0 - self.density.value(&r.at(t)) / majorant;
+            }
+        }
+        tr
+    }
+}
+
+impl<B: Clone + Hittable, D: Clone + DensityField, P: 'static + Clone + Material + Send + Sync>
+    Hittable for HeterogeneousMedium<B, D, P>
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let majorant = self.density.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+        //delta tracking: sample tentative collisions against the majorant and
+        //accept them with probability density / majorant
+        let ray_length = r.dir.length();
+        for (t0, t1) in InsideSegments::new(&self.boundary, r, t_min, t_max) {
+            let mut t = t0;
+            loop {
+                t -= (1.0 - random_0_1()).ln() / majorant / ray_length;
+                if t >= t1 {
+                    break;
+                }
+                let p = r.at(t);
+                if random_0_1() * majorant < self.density.value(&p) {
+                    let rec = HitRecord {
+                        p,
+                        normal: Vec3::new(1.0, 0.0, 0.0),
+                        t,
+                        front_face: true,
+                        mat_ptr: &self.phase_function,
+                        u: 0.0,
+                        v: 0.0,
+                        dpdu: Vec3::zero(),
+                        dpdv: Vec3::zero(),
+                        footprint: 0.0,
+                        incident: None,
+                        weight: Vec3::ones(),
+                        object_id: 0,
+                    };
+                    return Some(rec);
+                }
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+//Glow of a hot medium: black body colour of the local temperature, scaled with
+//the Stefan-Boltzmann T^4 law so that the hottest point reaches `intensity`.
+#[derive(Clone)]
+pub struct VolumeEmission<F: DensityField> {
+    pub temperature: F,
+    pub intensity: f64,
+    lut: Arc<Vec<Vec3>>,
+}
+
+impl<F: DensityField> VolumeEmission<F> {
+    const LUT_SIZE: usize = 256;
+
+    pub fn new(temperature: F, intensity: f64) -> Self {
+        let t_max = temperature.majorant();
+        let mut lut = Vec::with_capacity(Self::LUT_SIZE);
+        for i in 0..Self::LUT_SIZE {
+            let t = t_max * i as f64 / (Self::LUT_SIZE - 1) as f64;
+            let ratio = if t_max > 0.0 { t / t_max } else { 0.0 };
+            lut.push(blackbody_color(t) * (intensity * ratio.powi(4)));
+        }
+        Self {
+            temperature,
+            intensity,
+            lut: Arc::new(lut),
+        }
+    }
+
+    pub fn radiance(&self, p: &Vec3) -> Vec3 {
+        let t_max = self.temperature.majorant();
+        if t_max <= 0.0 {
+            return Vec3::zero();
+        }
+        let x = clamp(self.temperature.value(p) / t_max, 0.0, 1.0) * (Self::LUT_SIZE - 1) as f64;
+        let i = (x as usize).min(Self::LUT_SIZE - 2);
+        let f = x - i as f64;
+        self.lut[i] * (1.0 - f) + self.lut[i + 1] * f
+    }
+}
+
+impl<F: DensityField> Material for VolumeEmission<F> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, r: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.radiance(p)
+    }
+}
+
+//Absorbing, scattering and emitting medium. At every real collision found by
+//delta tracking the path scatters with probability `albedo` and is otherwise
+//absorbed, picking up the emission of the temperature field.
+#[derive(Clone)]
+pub struct EmissiveMedium<B: Hittable, D: DensityField, F: DensityField, P: Material> {
+    pub boundary: B,
+    pub density: D,
+    pub emission: VolumeEmission<F>,
+    pub phase_function: P,
+    pub albedo: f64,
+}
+
+impl<B: Hittable, D: DensityField, F: DensityField, P: Material> EmissiveMedium<B, D, F, P> {
+    pub fn new(b: B, d: D, temperature: F, intensity: f64, albedo: f64, p: P) -> Self {
+        Self {
+            boundary: b,
+            density: d,
+            emission: VolumeEmission::new(temperature, intensity),
+            phase_function: p,
+            albedo: clamp(albedo, 0.0, 1.0),
+        }
+    }
+}
+
+impl<
+        B: Clone + Hittable,
+        D: Clone + DensityField,
+        F: 'static + Clone + DensityField,
+        P: 'static + Clone + Material + Send + Sync,
+    > Hittable for EmissiveMedium<B, D, F, P>
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let majorant = self.density.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let ray_length = r.dir.length();
+        for (t0, t1) in InsideSegments::new(&self.boundary, r, t_min, t_max) {
+            let mut t = t0;
+            loop {
+                t -= (1.0 - random_0_1()).ln() / majorant / ray_length;
+                if t >= t1 {
+                    break;
+                }
+                let p = r.at(t);
+                if random_0_1() * majorant < self.density.value(&p) {
+                    let mat_ptr: &dyn Material = if random_0_1() < self.albedo {
+                        &self.phase_function
+                    } else {
+                        &self.emission
+                    };
+                    let rec = HitRecord {
+                        p,
+                        normal: Vec3::new(1.0, 0.0, 0.0),
+                        t,
+                        front_face: true,
+                        mat_ptr,
+                        u: 0.0,
+                        v: 0.0,
+                        dpdu: Vec3::zero(),
+                        dpdv: Vec3::zero(),
+                        footprint: 0.0,
+                        incident: None,
+                        weight: Vec3::ones(),
+                        object_id: 0,
+                    };
+                    return Some(rec);
+                }
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+//Homogeneous medium filling the scene below a height, no boundary needed. Rays
+//are clipped against the layer, so the fog they see does not depend on where
+//they start, and rays leaving upwards escape to the background.
+#[derive(Clone)]
+pub struct Atmosphere<P: Material> {
+    pub phase_function: P,
+    pub neg_inv_density: f64,
+    pub height: f64,
+}
+
+impl<P: Material> Atmosphere<P> {
+    pub fn new(d: f64, height: f64, p: P) -> Self {
+        Self {
+            phase_function: p,
+            neg_inv_density: -1.0 / d,
+            height,
+        }
+    }
+
+    //the part of [t_min, t_max] below the height, None if there is none
+    fn inside(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let (t0, t1) = if r.dir.y == 0.0 {
+            if r.orig.y >= self.height {
+                return None;
+            }
+            (t_min, t_max)
+        } else {
+            let t_height = (self.height - r.orig.y) / r.dir.y;
+            if r.dir.y > 0.0 {
+                (t_min, t_max.min(t_height))
+            } else {
+                (t_min.max(t_height), t_max)
+            }
+        };
+        if t0 < t1 {
+            Some((t0, t1))
+        } else {
+            None
+        }
+    }
+}
+
+impl<P: 'static + Clone + Material + Send + Sync> Hittable for Atmosphere<P> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t0, t1) = self.inside(r, t_min, t_max)?;
+        let hit_distance = self.neg_inv_density * random_0_1().ln();
+        let t = t0 + hit_distance / r.dir.length();
+        if t >= t1 {
+            return None;
+        }
+        let rec = HitRecord {
+            p: r.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            t,
+            front_face: true,
+            mat_ptr: &self.phase_function,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            footprint: 0.0,
+            incident: None,
+            weight: Vec3::ones(),
+            object_id: 0,
+        };
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        None
+    }
+}
+
+//Subsurface scattering by a volumetric random walk inside a closed boundary.
+//The boundary's own material is the interface, Dielectric::new(1.0) lets light
+//straight in. A ray starting inside walks until it reaches the boundary again and
+//the hit is the exit point, carrying the walk throughput. Free flights pick a
+//colour channel in proportion to the throughput and are weighted by the one sample
+//MIS over all channels, so chromatic mean free paths stay unbiased.
+//Inside and outside come from crossing parity, boundary normals are not trusted.
+#[derive(Clone)]
+pub struct Subsurface<B: Hittable> {
+    pub boundary: B,
+    pub sigma_t: Vec3,
+    pub albedo: Vec3,
+    pub g: f64,
+}
+
+impl<B: Hittable> Subsurface<B> {
+    const MAX_STEPS: i32 = 1024;
+
+    pub fn new(b: B, mean_free_path: Vec3, albedo: Vec3, g: f64) -> Self {
+        Self {
+            boundary: b,
+            sigma_t: Vec3::new(
+                1.0 / mean_free_path.x,
+                1.0 / mean_free_path.y,
+                1.0 / mean_free_path.z,
+            ),
+            albedo: Vec3::new(
+                clamp(albedo.x, 0.0, 1.0),
+                clamp(albedo.y, 0.0, 1.0),
+                clamp(albedo.z, 0.0, 1.0),
+            ),
+            g: clamp(g, -0.999, 0.999),
+        }
+    }
+
+    fn transmittance(&self, distance: f64) -> Vec3 {
+        Vec3::new(
+            (-self.sigma_t.x * distance).exp(),
+            (-self.sigma_t.y * distance).exp(),
+            (-self.sigma_t.z * distance).exp(),
+        )
+    }
+
+    //walks from a point inside until the boundary is crossed, `exit` being the
+    //crossing straight ahead. Returns the exit record, the ray that reached it,
+    //the throughput and the distance to the first event on the starting ray.
+    fn walk<'a>(&'a self, start: Ray, exit: HitRecord<'a>) -> (HitRecord<'a>, Ray, Vec3, f64) {
+        let mut ray = start;
+        let mut exit = exit;
+        let mut throughput = Vec3::ones();
+        let mut first_event = INF;
+        for _ in 0..Self::MAX_STEPS {
+            let total = throughput.x + throughput.y + throughput.z;
+            if total <= 0.0 {
+                break;
+            }
+            let u = random_0_1() * total;
+            let channel = if u < throughput.x {
+                0
+            } else if u < throughput.x + throughput.y {
+                1
+            } else {
+                2
+            };
+            let channel_pdf = throughput / total;
+            let distance = -(1.0 - random_0_1()).ln() / self.sigma_t.at(channel);
+            if first_event == INF {
+                first_event = distance.min(exit.t);
+            }
+            if distance >= exit.t {
+                let tr = self.transmittance(exit.t);
+                throughput = Vec3::elemul(throughput, tr) / (channel_pdf * tr);
+                return (exit, ray, throughput, first_event);
+            }
+            let tr = self.transmittance(distance);
+            throughput = Vec3::elemul(
+                throughput,
+                Vec3::elemul(Vec3::elemul(self.albedo, self.sigma_t), tr),
+            ) / (channel_pdf * Vec3::elemul(self.sigma_t, tr));
+            //the phase function is sampled exactly, its weight is one
+            let dir = HenyeyGreensteinPDF::new(&ray.dir, self.g).generate();
+            ray = Ray::new_with_lambda(ray.at(distance), dir.unit(), ray.tm, ray.lambda);
+            exit = match self.boundary.hit(&ray, 0.0, INF) {
+                Some(rec) => rec,
+                None => break,
+            };
+        }
+        (exit, ray, Vec3::zero(), first_event)
+    }
+}
+
+impl<B: Clone + Hittable> Hittable for Subsurface<B> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        //crossings are searched past t_max, the walk may start before a closer object
+        let mut rec = self.boundary.hit(r, t_min, INF)?;
+        let inside = match InsideSegments::new(&self.boundary, r, t_min, INF).next() {
+            Some((t0, _)) => t0 <= t_min,
+            None => false,
+        };
+        if !inside {
+            return if rec.t < t_max { Some(rec) } else { None };
+        }
+        let ray_length = r.dir.length();
+        let start = Ray::new_with_lambda(r.at(t_min), r.dir.unit(), r.tm, r.lambda);
+        rec.t = (rec.t - t_min) * ray_length;
+        let (mut exit, ray, weight, first_event) = self.walk(start, rec);
+        //the record is ordered by where the walk leaves the incoming ray
+        let t = t_min + first_event / ray_length;
+        if t >= t_max {
+            return None;
+        }
+        //the walk's last ray is moved back along itself to put the exit point at t,
+        //so that p and t agree on the ray the record is shaded with
+        exit.t = t;
+        exit.incident = Some(Ray::new_with_lambda(
+            exit.p - ray.dir * t,
+            ray.dir,
+            ray.tm,
+            ray.lambda,
+        ));
+        exit.weight = Vec3::elemul(exit.weight, weight);
+        Some(exit)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhNode;
+    use crate::hittable_list::HittableList;
+    use crate::material::{Dielectric, Isotropic, Lambertian};
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+
+    fn two_spheres() -> HittableList {
+        let mut list = HittableList::new();
+        for x in [0.0, 5.0].iter() {
+            list.add(Arc::new(Sphere::new(
+                Vec3::new(*x, 0.0, 0.0),
+                1.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+        }
+        list
+    }
+
+    #[test]
+    fn test_inside_segments_non_convex() {
+        let boundary = two_spheres();
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let segments: Vec<(f64, f64)> = InsideSegments::new(&boundary, &r, 0.001, INF).collect();
+        assert_eq!(segments.len(), 2);
+        assert!((segments[0].0 - 4.0).abs() < 1e-9 && (segments[0].1 - 6.0).abs() < 1e-9);
+        assert!((segments[1].0 - 9.0).abs() < 1e-9 && (segments[1].1 - 11.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_inside_segments_origin_inside() {
+        let boundary = two_spheres();
+        let r = Ray::new(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let segments: Vec<(f64, f64)> = InsideSegments::new(&boundary, &r, 0.001, 4.5).collect();
+        assert_eq!(segments.len(), 2);
+        assert!((segments[0].0 - 0.001).abs() < 1e-9 && (segments[0].1 - 1.0).abs() < 1e-9);
+        assert!((segments[1].0 - 4.0).abs() < 1e-9 && (segments[1].1 - 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_subsurface_hit_places_exit_at_t() {
+        let sss = Subsurface::new(
+            Sphere::new(Vec3::zero(), 1.0, Dielectric::new(1.0)),
+            Vec3::new(0.1, 0.2, 0.4),
+            Vec3::ones(),
+            0.0,
+        );
+        for _ in 0..100 {
+            let r = Ray::new(Vec3::zero(), Vec3::random_unit_vector() * 2.0, 0.0);
+            if let Some(rec) = sss.hit(&r, 0.001, INF) {
+                assert!((rec.incident.unwrap().at(rec.t) - rec.p).length() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_atmosphere_stays_beside_bvh() {
+        let mut list = two_spheres();
+        list.add(Arc::new(Atmosphere::new(
+            0.01,
+            100.0,
+            Isotropic::new(SolidColor::new_with_vec(Vec3::ones())),
+        )));
+        let bvh = BvhNode::new_with_list(&mut list, 0.0, 1.0).unwrap();
+        assert!(bvh.bounding_box(0.0, 1.0).is_some());
+        assert_eq!(list.objects.len(), 1);
+        assert!(list.objects[0].bounding_box(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_atmosphere_transmittance_ignores_ray_start() {
+        //fog of density 0.5 below y = 0
+        let fog = Atmosphere::new(
+            0.5,
+            0.0,
+            Isotropic::new(SolidColor::new_with_vec(Vec3::ones())),
+        );
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let slanted = Vec3::new(3.0, -4.0, 0.0);
+        //each ray spends a length of 2 in the fog, wherever it starts
+        let rays = [
+            (Ray::new(Vec3::new(0.0, 5.0, 0.0), down, 0.0), 7.0),
+            (Ray::new(Vec3::new(0.0, -1.0, 0.0), down, 0.0), 2.0),
+            (Ray::new(Vec3::new(0.0, 8.0, 0.0), slanted * 0.1, 0.0), 24.0),
+            (Ray::new(Vec3::new(0.0, -2.0, 0.0), -down, 0.0), INF),
+        ];
+        let n = 20_000;
+        for (r, t_max) in rays.iter() {
+            let passed = (0..n).filter(|_| fog.hit(r, 0.0, *t_max).is_none()).count();
+            let transmittance = passed as f64 / n as f64;
+            let expected = (-0.5f64 * 2.0).exp();
+            assert!((transmittance - expected).abs() < 0.02, "{}", transmittance);
+        }
+    }
+
+    #[test]
+    fn test_heterogeneous_constant_field_transmittance() {
+        //a chord of length 2 through the middle and one of length 1.2 off it
+        let chords = [
+            (
+                Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0),
+                2.0,
+            ),
+            (
+                Ray::new(Vec3::new(-3.0, 0.8, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0),
+                1.2,
+            ),
+        ];
+        let boundary = || Sphere::new(Vec3::zero(), 1.0, Dielectric::new(1.0));
+        let phase = || Isotropic::new(SolidColor::new_with_vec(Vec3::ones()));
+        let grid = GridDensity::new(
+            vec![1.0; 8],
+            2,
+            2,
+            2,
+            Vec3::ones() * -1.0,
+            Vec3::ones(),
+            0.8,
+        )
+        .unwrap();
+        //the texture field stays at half its majorant, so tracking rejects collisions
+        let half = TextureDensity::new(SolidColor::new_with_col(0.5, 0.5, 0.5), 1.6);
+        let grid = HeterogeneousMedium::new(boundary(), grid, phase());
+        let half = HeterogeneousMedium::new(boundary(), half, phase());
+        let n = 20_000;
+        for (r, d) in chords.iter() {
+            let expected = (-0.8f64 * d).exp();
+            let passed = |medium: &dyn Hittable| {
+                (0..n)
+                    .filter(|_| medium.hit(r, 0.001, INF).is_none())
+                    .count() as f64
+                    / n as f64
+            };
+            assert!((passed(&grid) - expected).abs() < 0.02, "{}", passed(&grid));
+            assert!((passed(&half) - expected).abs() < 0.02, "{}", passed(&half));
+            let ratio = (0..n)
+                .map(|_| half.transmittance(r, 0.001, INF))
+                .sum::<f64>()
+                / n as f64;
+            assert!((ratio - expected).abs() < 0.02, "{}", ratio);
+        }
+        assert!(GridDensity::new(vec![1.0; 7], 2, 2, 2, Vec3::zero(), Vec3::ones(), 1.0).is_err());
+    }
+
+    #[test]
+    fn test_subsurface_walk_conserves_energy() {
+        let sss = Subsurface::new(
+            Sphere::new(Vec3::zero(), 1.0, Dielectric::new(1.0)),
+            Vec3::new(0.1, 0.2, 0.4),
+            Vec3::ones(),
+            0.3,
+        );
+        let n = 2000;
+        let mut sum = Vec3::zero();
+        for _ in 0..n {
+            let start = Ray::new(Vec3::zero(), Vec3::random_unit_vector(), 0.0);
+            let exit = sss.boundary.hit(&start, 0.0, INF).unwrap();
+            let (exit, _, weight, _) = sss.walk(start, exit);
+            assert!((exit.p.length() - 1.0).abs() < 1e-6);
+            sum += weight;
+        }
+        assert!((sum / n as f64 - Vec3::ones()).length() < 0.1);
+    }
+}
@@ -0,0 +1,468 @@
+use crate::bdpt::Bdpt;
+use crate::camera::Camera;
+use crate::checkpoint::Checkpoint;
+use crate::emitter::Emitters;
+use crate::film::Splat;
+use crate::hittable_list::HittableList;
+use crate::integrator::Integrator;
+use crate::render::{pass_light_pdf, pass_seed, sample_budget, RenderSettings, PASS_SAMPLES};
+use crate::vec3::Vec3;
+use crate::{random_0_1, seed_random, with_sampler, Sampler, PI};
+use indicatif::ProgressBar;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+//standard deviation of a small step in primary sample space
+const SIGMA: f64 = 0.01;
+//chance a mutation draws all of its samples anew
+const LARGE_STEP_PROBABILITY: f64 = 0.3;
+//most paths a pass traces to estimate the image's brightness and pick chain starts
+const BOOTSTRAP_SAMPLES: u64 = 100_000;
+
+//a primary sample with the value it had before the current mutation
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    //iteration the value was last mutated in
+    modified: u64,
+    backup_value: f64,
+    backup_modified: u64,
+}
+
+//Primary sample vector of a Markov chain, handed out in order to the
+//integrator through random_0_1. Samples are mutated lazily when they are
+//asked for, a large step replaces them all, a small step moves each by a
+//normally distributed offset. Its own randomness comes from a generator
+//seeded per chain, never from random_0_1.
+pub struct MltSampler {
+    rng: StdRng,
+    samples: Vec<PrimarySample>,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    index: usize,
+}
+
+impl MltSampler {
+    //the first path of a sampler is a large step, so equal seeds give equal paths
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            samples: Vec::new(),
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            index: 0,
+        }
+    }
+
+    //begins the next mutation
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < LARGE_STEP_PROBABILITY;
+        self.index = 0;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    //puts back the samples the rejected mutation changed
+    pub fn reject(&mut self) {
+        for s in self.samples.iter_mut() {
+            if s.modified == self.iteration {
+                s.value = s.backup_value;
+                s.modified = s.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    //a number in [0,1) for the acceptance test, apart from the primary samples
+    pub fn uniform(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    //applies the mutations the sample missed since it was last asked for
+    fn ensure_ready(&mut self, index: usize) {
+        //new samples start out uniform as of the last large step
+        while self.samples.len() <= index {
+            let value = self.rng.gen();
+            self.samples.push(PrimarySample {
+                value,
+                modified: self.last_large_step,
+                ..PrimarySample::default()
+            });
+        }
+        let s = &mut self.samples[index];
+        if s.modified < self.last_large_step {
+            s.value = self.rng.gen();
+            s.modified = self.last_large_step;
+        }
+        s.backup_value = s.value;
+        s.backup_modified = s.modified;
+        if self.large_step {
+            s.value = self.rng.gen();
+        } else {
+            //the small steps since it was last used add up to one wider step
+            let steps = (self.iteration - s.modified) as f64;
+            let u1 = 1.0 - self.rng.gen::<f64>();
+            let u2 = self.rng.gen::<f64>();
+            let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+            s.value += normal * SIGMA * steps.sqrt();
+            s.value = (s.value - s.value.floor()).min(1.0 - f64::EPSILON);
+        }
+        s.modified = self.iteration;
+    }
+}
+
+impl Sampler for MltSampler {
+    fn next_1d(&mut self) -> f64 {
+        let index = self.index;
+        self.ensure_ready(index);
+        self.index += 1;
+        self.samples[index].value
+    }
+}
+
+//the splats of one path and their brightness, the chain's target function
+struct Path {
+    splats: Vec<Splat>,
+    f: f64,
+}
+
+//A bidirectional path through a pixel the sampler picks. The camera
+//subpath's radiance lands in its pixel unfiltered.
+fn sample_path(bdpt: &Bdpt, cam: &Camera, width: u32, height: u32) -> Path {
+    let px = random_0_1() * width as f64;
+    let py = random_0_1() * height as f64;
+    let r = cam.get_ray(px / width as f64, 1.0 - py / height as f64);
+    let mut splats = Vec::new();
+    let color = bdpt.sample(&r, width, height, &mut splats);
+    splats.push(Splat {
+        x: (px as u32).min(width - 1),
+        y: (py as u32).min(height - 1),
+        color,
+    });
+    let f: f64 = splats.iter().map(|s| s.color.luminance()).sum();
+    //NaN paths are never accepted
+    let f = if f.is_finite() && f > 0.0 { f } else { 0.0 };
+    Path { splats, f }
+}
+
+fn evaluate(
+    sampler: &Rc<RefCell<MltSampler>>,
+    bdpt: &Bdpt,
+    cam: &Camera,
+    width: u32,
+    height: u32,
+) -> Path {
+    let installed: Rc<RefCell<dyn Sampler>> = sampler.clone();
+    with_sampler(installed, || sample_path(bdpt, cam, width, height))
+}
+
+fn add_splats(image: &mut [Vec3], width: u32, path: &Path, scale: f64) {
+    for splat in path.splats.iter() {
+        image[(splat.y * width + splat.x) as usize] += splat.color * scale;
+    }
+}
+
+//the pass's integrator on a worker, picking lights by the pass's light_pdf
+fn pass_bdpt<'a>(
+    world: &'a HittableList,
+    lights: &'a HittableList,
+    cam: &'a Camera,
+    settings: &RenderSettings,
+    light_pdf: &[f64],
+) -> Bdpt<'a> {
+    let emitters = Emitters::with_light_pdf(world, lights, settings.background, light_pdf.to_vec());
+    Bdpt::with_emitters(world, cam, emitters, settings.max_depth)
+}
+
+//Adds up to PASS_SAMPLES mutations per pixel to the checkpoint's film with
+//primary sample space Metropolis light transport over bidirectional paths.
+//A pass estimates the image brightness from bootstrap paths, starts the
+//chains at bootstrap paths picked in proportion to their brightness and runs
+//them round robin on one worker per core. Returns false once the budget is
+//spent.
+pub fn render_pass(
+    world: &HittableList,
+    lights: &HittableList,
+    cam: &Camera,
+    settings: &RenderSettings,
+    state: &mut Checkpoint,
+    bar: &ProgressBar,
+) -> bool {
+    let chains = match settings.integrator {
+        Integrator::Mlt { chains } => chains as u64,
+        _ => return false,
+    };
+    let (width, height) = (settings.image_width, settings.image_height);
+    //samples_per_pixel mutations per pixel
+    let left = sample_budget(settings).saturating_sub(state.film.light_paths);
+    if left == 0 {
+        return false;
+    }
+    let mutations = left.min(PASS_SAMPLES as u64 * width as u64 * height as u64);
+    let chains = chains.min(mutations);
+    let n_bootstrap = BOOTSTRAP_SAMPLES.min(mutations).max(chains);
+    let (seed, pass) = (state.seed, state.passes);
+    let n_workers = num_cpus::get().max(1);
+    let world = Arc::new(world.clone());
+    let lights = Arc::new(lights.clone());
+    let settings = *settings;
+    let cam = *cam;
+    let bootstrap_seed = move |i: u64| pass_seed(seed, pass, (1 << 62) | i);
+    //lights are picked alike on every worker, so a chain start weighs what its
+    //bootstrap path did
+    let light_pdf = Arc::new(pass_light_pdf(
+        &world,
+        &lights,
+        settings.background,
+        seed,
+        pass,
+    ));
+
+    //the brightness of bootstrap path i, every worker takes every n_workers-th
+    let (tx, rx) = channel();
+    let mut workers = Vec::with_capacity(n_workers);
+    for worker in 0..n_workers {
+        let tx = tx.clone();
+        let world = world.clone();
+        let lights = lights.clone();
+        let light_pdf = light_pdf.clone();
+        workers.push(thread::spawn(move || {
+            seed_random(pass_seed(seed, pass, (1 << 61) | worker as u64));
+            let bdpt = pass_bdpt(&world, &lights, &cam, &settings, &light_pdf);
+            for i in (worker as u64..n_bootstrap).step_by(n_workers) {
+                let sampler = Rc::new(RefCell::new(MltSampler::new(bootstrap_seed(i))));
+                let path = evaluate(&sampler, &bdpt, &cam, width, height);
+                if tx.send((i, path.f)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+    drop(tx);
+    let mut weights = vec![0.0; n_bootstrap as usize];
+    for (i, f) in rx.iter() {
+        weights[i as usize] = f;
+    }
+    join(workers);
+    let total: f64 = weights.iter().sum();
+    //mean brightness of the image, the normalization of the chains' splats
+    let b = total / n_bootstrap as f64;
+    if b > 0.0 {
+        let mut rng = StdRng::seed_from_u64(pass_seed(seed, pass, 3 << 62));
+        let starts: Vec<u64> = (0..chains)
+            .map(|_| {
+                let mut left = rng.gen::<f64>() * total;
+                let mut start = 0;
+                for (i, f) in weights.iter().enumerate() {
+                    if *f > 0.0 {
+                        start = i;
+                        if left < *f {
+                            break;
+                        }
+                        left -= f;
+                    }
+                }
+                start as u64
+            })
+            .collect();
+        let starts = Arc::new(starts);
+
+        let (tx, rx) = channel();
+        let mut workers = Vec::with_capacity(n_workers);
+        for worker in 0..n_workers {
+            let tx = tx.clone();
+            let world = world.clone();
+            let lights = lights.clone();
+            let light_pdf = light_pdf.clone();
+            let starts = starts.clone();
+            workers.push(thread::spawn(move || {
+                seed_random(pass_seed(seed, pass, (1 << 61) | worker as u64));
+                let bdpt = pass_bdpt(&world, &lights, &cam, &settings, &light_pdf);
+                let mut image = vec![Vec3::zero(); (width * height) as usize];
+                for chain in (worker as u64..chains).step_by(n_workers) {
+                    //the chain's share of the pass, the first ones take the remainder
+                    let steps = mutations / chains + (chain < mutations % chains) as u64;
+                    let start = starts[chain as usize];
+                    let sampler = Rc::new(RefCell::new(MltSampler::new(bootstrap_seed(start))));
+                    let mut current = evaluate(&sampler, &bdpt, &cam, width, height);
+                    for _ in 0..steps {
+                        sampler.borrow_mut().start_iteration();
+                        let proposed = evaluate(&sampler, &bdpt, &cam, width, height);
+                        let accept = if current.f > 0.0 {
+                            (proposed.f / current.f).min(1.0)
+                        } else {
+                            1.0
+                        };
+                        //both paths are splatted by their expected share of the step,
+                        //a path carrying nothing has nothing to splat
+                        if accept > 0.0 && proposed.f > 0.0 {
+                            add_splats(&mut image, width, &proposed, accept / proposed.f);
+                        }
+                        if accept < 1.0 && current.f > 0.0 {
+                            add_splats(&mut image, width, &current, (1.0 - accept) / current.f);
+                        }
+                        if sampler.borrow_mut().uniform() < accept {
+                            current = proposed;
+                            sampler.borrow_mut().accept();
+                        } else {
+                            sampler.borrow_mut().reject();
+                        }
+                    }
+                }
+                if tx.send(image).is_err() {
+                    return;
+                }
+            }));
+        }
+        drop(tx);
+        let images: Vec<Vec<Vec3>> = rx.iter().collect();
+        join(workers);
+        for image in images.iter() {
+            for (i, color) in image.iter().enumerate() {
+                if *color != Vec3::zero() {
+                    state.film.add_splat(&Splat {
+                        x: i as u32 % width,
+                        y: i as u32 / width,
+                        color: *color * b,
+                    });
+                }
+            }
+        }
+    }
+    //splats are averaged over the mutations like over bdpt's light paths
+    state.film.add_light_paths(mutations);
+    bar.inc(mutations);
+    state.passes += 1;
+    true
+}
+
+fn join(workers: Vec<thread::JoinHandle<()>>) {
+    for worker in workers {
+        if let Err(e) = worker.join() {
+            std::panic::resume_unwind(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::render::mean_luminance;
+    use crate::sphere::Sphere;
+    use crate::texture::{CheckerTexture, SolidColor};
+
+    #[test]
+    fn test_matches_path_tracing() {
+        //a ball on a floor under two lights, the checkered one's power estimate
+        //depends on the points it is sampled at
+        let grey = Lambertian::new(SolidColor::new_with_col(0.5, 0.5, 0.5));
+        let black = || Lambertian::new(SolidColor::new_with_vec(Vec3::zero()));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, -100.0, 0.0),
+            100.0,
+            grey.clone(),
+        )));
+        world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, grey)));
+        let light = DiffuseLight::new(SolidColor::new_with_col(20.0, 20.0, 20.0));
+        world.add(Arc::new(Sphere::new(Vec3::new(-1.5, 3.5, 1.0), 0.5, light)));
+        let checker = DiffuseLight::new(CheckerTexture::new(
+            SolidColor::new_with_col(10.0, 10.0, 10.0),
+            SolidColor::new_with_col(1.0, 1.0, 1.0),
+        ));
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(1.5, 3.0, 1.0),
+            0.6,
+            checker,
+        )));
+        let mut lights = HittableList::new();
+        lights.add(Arc::new(Sphere::new(
+            Vec3::new(-1.5, 3.5, 1.0),
+            0.5,
+            black(),
+        )));
+        lights.add(Arc::new(Sphere::new(
+            Vec3::new(1.5, 3.0, 1.0),
+            0.6,
+            black(),
+        )));
+        let settings = RenderSettings {
+            image_width: 32,
+            image_height: 24,
+            samples_per_pixel: 64,
+            max_depth: 5,
+            background: Vec3::zero(),
+            spectral: false,
+            noise_threshold: 0.0,
+            filter: Filter::pixel_box(),
+            split_lighting: false,
+            features: false,
+            integrator: Integrator::Path,
+        };
+        let mut cam = Camera::new(
+            Vec3::new(0.0, 2.0, 6.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            50.0,
+            4.0 / 3.0,
+            0.0,
+            6.0,
+            0.0,
+            1.0,
+        );
+        cam.set_image_height(settings.image_height);
+        let mean = |integrator| {
+            let settings = RenderSettings {
+                integrator,
+                ..settings
+            };
+            mean_luminance(&world, &lights, &cam, &settings, 5)
+        };
+        let path = mean(Integrator::Path);
+        let mlt = mean(Integrator::Mlt { chains: 64 });
+        assert!(path > 0.05);
+        assert!((mlt - path).abs() < 0.05 * path, "{} {}", mlt, path);
+    }
+
+    #[test]
+    fn test_sampler_removed_after_panic() {
+        let sampler: Rc<RefCell<dyn Sampler>> = Rc::new(RefCell::new(MltSampler::new(7)));
+        let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            with_sampler(sampler.clone(), || -> f64 { panic!("path failed") })
+        }));
+        assert!(unwound.is_err());
+        //the thread's generator is back, seeded alike it repeats itself
+        seed_random(3);
+        let a = random_0_1();
+        seed_random(3);
+        assert_eq!(a, random_0_1());
+    }
+
+    #[test]
+    fn test_reject_restores_samples() {
+        let mut sampler = MltSampler::new(7);
+        let first: Vec<f64> = (0..8).map(|_| sampler.next_1d()).collect();
+        sampler.start_iteration();
+        let mutated: Vec<f64> = (0..12).map(|_| sampler.next_1d()).collect();
+        assert_ne!(first[..], mutated[..8]);
+        sampler.reject();
+
+        let restored: Vec<f64> = sampler.samples.iter().take(8).map(|s| s.value).collect();
+        assert_eq!(first, restored);
+        assert_eq!(sampler.iteration, 0);
+    }
+}
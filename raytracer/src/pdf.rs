@@ -1,14 +1,25 @@
 use crate::onb::ONB;
 use crate::vec3::Vec3;
-use crate::{random_0_1, Hittable, PI};
+use crate::{clamp, random_0_1, Hittable, PI};
 use std::sync::Arc;
 
-pub trait PDF {
+pub trait PDF: Sync + Send {
     fn value(&self, direction: &Vec3) -> f64;
 
     fn generate(&self) -> Vec3;
 }
 
+impl<T: PDF + ?Sized> PDF for Arc<T> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        (**self).value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        (**self).generate()
+    }
+}
+
+#[derive(Clone)]
 pub struct CosinePDF {
     pub uvw: ONB,
 }
@@ -36,21 +47,22 @@ impl PDF for CosinePDF {
     }
 }
 
-pub struct HittablePDF {
+#[derive(Clone)]
+pub struct HittablePDF<'a, T: Hittable + ?Sized> {
     pub orig: Vec3,
-    pub ptr: Arc<dyn Hittable>,
+    pub ptr: &'a T,
 }
 
-impl HittablePDF {
-    pub fn new(p: &Arc<dyn Hittable>, origin: &Vec3) -> Self {
+impl<'a, T: Hittable + ?Sized> HittablePDF<'a, T> {
+    pub fn new(p: &'a T, origin: &Vec3) -> Self {
         Self {
-            ptr: p.clone(),
+            ptr: p,
             orig: origin.clone(),
         }
     }
 }
 
-impl PDF for HittablePDF {
+impl<T: Hittable + ?Sized> PDF for HittablePDF<'_, T> {
     fn value(&self, direction: &Vec3) -> f64 {
         self.ptr.pdf_value(&self.orig, direction)
     }
@@ -60,18 +72,19 @@ impl PDF for HittablePDF {
     }
 }
 
-pub struct MixturePDF {
-    p0: Arc<dyn PDF>,
-    p1: Arc<dyn PDF>,
+#[derive(Clone)]
+pub struct MixturePDF<U: PDF, V: PDF> {
+    p0: U,
+    p1: V,
 }
 
-impl MixturePDF {
-    pub fn new(p0: Arc<dyn PDF>, p1: Arc<dyn PDF>) -> Self {
+impl<U: PDF, V: PDF> MixturePDF<U, V> {
+    pub fn new(p0: U, p1: V) -> Self {
         Self { p0, p1 }
     }
 }
 
-impl PDF for MixturePDF {
+impl<U: PDF, V: PDF> PDF for MixturePDF<U, V> {
     fn value(&self, direction: &Vec3) -> f64 {
         0.5 * self.p0.value(direction) + 0.5 * self.p1.value(direction)
     }
@@ -84,3 +97,134 @@ impl PDF for MixturePDF {
         }
     }
 }
+
+#[derive(Clone)]
+pub struct SpherePDF {}
+
+impl SpherePDF {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PDF for SpherePDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        Vec3::random_unit_vector()
+    }
+}
+
+//g > 0 scatters forward along the incoming propagation direction w
+#[derive(Clone)]
+pub struct HenyeyGreensteinPDF {
+    pub uvw: ONB,
+    pub g: f64,
+}
+
+impl HenyeyGreensteinPDF {
+    pub fn new(w: &Vec3, g: f64) -> Self {
+        let mut _uvw = ONB::new();
+        _uvw.build_from_w(w.clone());
+        Self { uvw: _uvw, g }
+    }
+
+    pub fn phase(cos_theta: f64, g: f64) -> f64 {
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+    pub fn sample(&self) -> Vec3 {
+        let g = self.g;
+        let r1 = random_0_1();
+        let r2 = random_0_1();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * r1
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * r1);
+            (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        };
+        let cos_theta = clamp(cos_theta, -1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * r2;
+        self.uvw
+            .local_with_f64(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+}
+
+impl PDF for HenyeyGreensteinPDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        Self::phase(direction.unit() * self.uvw.w, self.g)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.sample()
+    }
+}
+
+//w * HG(g1) + (1 - w) * HG(g2), e.g. a strong forward lobe plus a weak back lobe
+#[derive(Clone)]
+pub struct DoubleHenyeyGreensteinPDF {
+    pub lobe1: HenyeyGreensteinPDF,
+    pub lobe2: HenyeyGreensteinPDF,
+    pub weight: f64,
+}
+
+impl DoubleHenyeyGreensteinPDF {
+    pub fn new(w: &Vec3, g1: f64, g2: f64, weight: f64) -> Self {
+        Self {
+            lobe1: HenyeyGreensteinPDF::new(w, g1),
+            lobe2: HenyeyGreensteinPDF::new(w, g2),
+            weight,
+        }
+    }
+}
+
+impl PDF for DoubleHenyeyGreensteinPDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.weight * self.lobe1.value(direction)
+            + (1.0 - self.weight) * self.lobe2.value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_0_1() < self.weight {
+            self.lobe1.sample()
+        } else {
+            self.lobe2.sample()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_henyey_greenstein_normalized() {
+        //integrate over cos_theta with the azimuth done analytically
+        for g in [-0.7, 0.0, 0.3, 0.9].iter() {
+            let n = 100000;
+            let mut sum = 0.0;
+            for i in 0..n {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                sum += 2.0 * PI * HenyeyGreensteinPDF::phase(cos_theta, *g) * 2.0 / n as f64;
+            }
+            assert!((sum - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_henyey_greenstein_mean_cosine() {
+        let w = Vec3::new(0.0, 0.0, 1.0);
+        let pdf = HenyeyGreensteinPDF::new(&w, 0.6);
+        let n = 200000;
+        let mut mean = 0.0;
+        for _ in 0..n {
+            mean += pdf.generate() * w;
+        }
+        mean /= n as f64;
+        assert!((mean - 0.6).abs() < 0.01);
+    }
+}
@@ -0,0 +1,543 @@
+use crate::emitter::{scene_bounds, Emitters};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::integrator::light_index;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::{random_0_1, seed_random, INF, PI};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+//photons a radiance estimate gathers from the global and the caustic map
+const GLOBAL_NEAREST: usize = 64;
+const CAUSTIC_NEAREST: usize = 32;
+//largest gather radius, as a fraction of the scene's bounding radius
+const MAX_RADIUS: f64 = 0.03;
+//photons a batch of PhotonMaps::trace shoots
+const BATCH_PHOTONS: u32 = 10_000;
+//bounces before russian roulette may end a photon
+const ROULETTE_DEPTH: usize = 3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Photon {
+    pub p: Vec3,
+    //unit direction the photon arrived along
+    pub dir: Vec3,
+    pub power: Vec3,
+}
+
+//Balanced kd-tree over photon positions. The photons are reordered so each
+//subrange holds its median in the middle, split along the axis of the range's
+//largest extent.
+pub struct KdTree {
+    photons: Vec<Photon>,
+    axes: Vec<i32>,
+}
+
+//a photon found by KdTree::nearest, ordered by distance for the max-heap
+struct Near {
+    dist2: f64,
+    index: usize,
+}
+
+impl PartialEq for Near {
+    fn eq(&self, other: &Self) -> bool {
+        self.dist2 == other.dist2
+    }
+}
+
+impl Eq for Near {}
+
+impl PartialOrd for Near {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Near {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist2
+            .partial_cmp(&other.dist2)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl KdTree {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        Self::build(&mut photons, &mut axes);
+        Self { photons, axes }
+    }
+
+    fn build(photons: &mut [Photon], axes: &mut [i32]) {
+        if photons.is_empty() {
+            return;
+        }
+        let (mut lo, mut hi) = (photons[0].p, photons[0].p);
+        for photon in photons.iter() {
+            lo = Vec3::new(
+                lo.x.min(photon.p.x),
+                lo.y.min(photon.p.y),
+                lo.z.min(photon.p.z),
+            );
+            hi = Vec3::new(
+                hi.x.max(photon.p.x),
+                hi.y.max(photon.p.y),
+                hi.z.max(photon.p.z),
+            );
+        }
+        let extent = hi - lo;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |a, b| {
+            a.p.at(axis)
+                .partial_cmp(&b.p.at(axis))
+                .unwrap_or(Ordering::Equal)
+        });
+        axes[mid] = axis;
+        let (left, right) = photons.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+        Self::build(left, left_axes);
+        Self::build(&mut right[1..], &mut right_axes[1..]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    //The k photons nearest to p within sqrt(max_dist2), and the squared radius
+    //of the disk they were gathered from.
+    pub fn nearest(&self, p: Vec3, k: usize, max_dist2: f64) -> (Vec<&Photon>, f64) {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        let mut dist2 = max_dist2;
+        self.search(0, self.photons.len(), p, k, &mut dist2, &mut heap);
+        let near = heap.iter().map(|n| &self.photons[n.index]).collect();
+        (near, dist2)
+    }
+
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        p: Vec3,
+        k: usize,
+        dist2: &mut f64,
+        heap: &mut BinaryHeap<Near>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+        let axis = self.axes[mid];
+        let d = p.at(axis) - photon.p.at(axis);
+        let (near, far) = if d < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near.0, near.1, p, k, dist2, heap);
+        let to_photon = (photon.p - p).squared_length();
+        if to_photon < *dist2 {
+            heap.push(Near {
+                dist2: to_photon,
+                index: mid,
+            });
+            if heap.len() > k {
+                heap.pop();
+            }
+            if heap.len() == k {
+                *dist2 = heap.peek().map_or(*dist2, |n| n.dist2);
+            }
+        }
+        if d * d < *dist2 {
+            self.search(far.0, far.1, p, k, dist2, heap);
+        }
+    }
+}
+
+//Photons shot from the lights for one pass of the photon integrator. Every
+//diffuse hit stores one in the global map, those that got there only through
+//specular bounces in the caustic map too.
+pub struct PhotonMaps {
+    global: KdTree,
+    caustic: KdTree,
+    max_dist2: f64,
+}
+
+impl PhotonMaps {
+    //Shoots photons from the lights on one worker per core, in batches of
+    //BATCH_PHOTONS, picking lights by the pass's light_pdf. Batch i draws from
+    //the random stream seed(i + 1), so the maps do not depend on which worker
+    //traced what.
+    pub fn trace(
+        world: &Arc<HittableList>,
+        lights: &Arc<HittableList>,
+        light_pdf: &Arc<Vec<f64>>,
+        background: Vec3,
+        max_depth: i32,
+        photons: u32,
+        seed: impl Fn(u64) -> u64 + Copy + Send + 'static,
+    ) -> Self {
+        let n_batches = (photons as f64 / BATCH_PHOTONS as f64).ceil() as u32;
+        let n_workers = num_cpus::get().max(1);
+        let (tx, rx) = channel();
+        let mut workers = Vec::with_capacity(n_workers);
+        for worker in 0..n_workers {
+            let tx = tx.clone();
+            let world = world.clone();
+            let lights = lights.clone();
+            let light_pdf = light_pdf.clone();
+            workers.push(thread::spawn(move || {
+                let emitters =
+                    Emitters::with_light_pdf(&world, &lights, background, light_pdf.to_vec());
+                for batch in (worker as u32..n_batches).step_by(n_workers) {
+                    seed_random(seed(batch as u64 + 1));
+                    let count = BATCH_PHOTONS.min(photons - batch * BATCH_PHOTONS);
+                    let shot = shoot(&emitters, &world, max_depth, count, photons);
+                    if tx.send((batch, shot)).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        drop(tx);
+        let mut batches: Vec<_> = rx.iter().collect();
+        for worker in workers {
+            if let Err(e) = worker.join() {
+                std::panic::resume_unwind(e);
+            }
+        }
+        batches.sort_by_key(|(batch, _)| *batch);
+        let mut global = Vec::new();
+        let mut caustic = Vec::new();
+        for (_, (g, c)) in batches {
+            global.extend(g);
+            caustic.extend(c);
+        }
+        let max_dist = MAX_RADIUS * scene_bounds(world).1;
+        Self {
+            global: KdTree::new(global),
+            caustic: KdTree::new(caustic),
+            max_dist2: max_dist * max_dist,
+        }
+    }
+
+    //Radiance leaving the hit back along r_in, from the photons around it.
+    //attenuation is the material's at the hit.
+    fn estimate(
+        &self,
+        tree: &KdTree,
+        k: usize,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: Vec3,
+    ) -> Vec3 {
+        let (near, dist2) = tree.nearest(rec.p, k, self.max_dist2);
+        let mut sum = Vec3::zero();
+        for photon in near {
+            //photons arriving from behind light the other side
+            let wi = -photon.dir;
+            let cosine = rec.normal * wi;
+            if cosine <= 0.0 {
+                continue;
+            }
+            let scattered = Ray::new(rec.p, wi, r_in.tm);
+            let f = rec.mat_ptr.scattering_value(r_in, rec, &scattered) / cosine;
+            sum += Vec3::elemul(photon.power, attenuation) * f;
+        }
+        if dist2 > 0.0 {
+            sum / (PI * dist2)
+        } else {
+            Vec3::zero()
+        }
+    }
+}
+
+//Traces count of the photons shot from the emitters, each carrying its share
+//of all photons. Returns the global and the caustic photons.
+fn shoot(
+    emitters: &Emitters,
+    world: &HittableList,
+    max_depth: i32,
+    count: u32,
+    photons: u32,
+) -> (Vec<Photon>, Vec<Photon>) {
+    let mut global = Vec::new();
+    let mut caustic = Vec::new();
+    for _ in 0..count {
+        //the scenes' shutters are open over [0, 1)
+        let e = match emitters.sample_emission(random_0_1()) {
+            Some(e) => e,
+            None => continue,
+        };
+        let mut beta = e.beta() / photons as f64;
+        let mut throughput = Vec3::ones();
+        let mut ray = e.ray;
+        //specular bounces only since the light, and at least one
+        let mut specular_only = true;
+        let mut is_caustic = false;
+        for bounce in 0..max_depth.max(0) as usize {
+            let rec = match world.hit(&ray, 0.001, INF) {
+                Some(rec) => rec,
+                None => break,
+            };
+            let r_in = rec.incident.unwrap_or(ray);
+            let mat = rec.mat_ptr;
+            let s_rec = match mat.scatter(&r_in, &rec) {
+                Some(s_rec) => s_rec,
+                None => break,
+            };
+            let attenuation = Vec3::elemul(s_rec.attenuation, rec.weight);
+            if s_rec.is_specular {
+                //photons carry importance, which refraction scales
+                let eta = s_rec.eta * s_rec.eta;
+                beta = Vec3::elemul(beta, attenuation) * eta;
+                throughput = Vec3::elemul(throughput, attenuation) * eta;
+                is_caustic = specular_only;
+                ray = s_rec.specular_ray;
+            } else {
+                if !mat.is_volumetric() {
+                    let photon = Photon {
+                        p: rec.p,
+                        dir: r_in.dir.unit(),
+                        power: beta,
+                    };
+                    global.push(photon);
+                    if is_caustic {
+                        caustic.push(photon);
+                    }
+                }
+                specular_only = false;
+                is_caustic = false;
+                let dir = s_rec.pdf_ptr.generate();
+                let pdf = s_rec.pdf_ptr.value(&dir);
+                let scattered = Ray::new(rec.p, dir, ray.tm);
+                let f = attenuation * mat.scattering_value(&r_in, &rec, &scattered);
+                if pdf.is_nan() || pdf <= 0.0 || f == Vec3::zero() {
+                    break;
+                }
+                beta = Vec3::elemul(beta, f) / pdf;
+                throughput = Vec3::elemul(throughput, f) / pdf;
+                ray = scattered;
+            }
+            if bounce + 1 >= ROULETTE_DEPTH {
+                let q = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+                if random_0_1() >= q {
+                    break;
+                }
+                beta /= q;
+                throughput /= q;
+            }
+        }
+    }
+    (global, caustic)
+}
+
+//Photon mapping after Jensen's "Realistic Image Synthesis Using Photon Mapping".
+//Camera rays follow specular bounces to the first diffuse hit, which takes
+//direct light from a sampled light, caustics from the caustic map and the rest
+//from one final gather ray looking up the global map where it lands. Only the
+//lights list and the background shoot photons. RGB only.
+pub struct PhotonMapper<'a> {
+    world: &'a HittableList,
+    emitters: Emitters<'a>,
+    maps: &'a PhotonMaps,
+    max_depth: i32,
+}
+
+impl<'a> PhotonMapper<'a> {
+    pub fn new(
+        world: &'a HittableList,
+        lights: &'a HittableList,
+        background: Vec3,
+        maps: &'a PhotonMaps,
+        max_depth: i32,
+    ) -> Self {
+        Self::with_emitters(
+            world,
+            Emitters::new(world, lights, background),
+            maps,
+            max_depth,
+        )
+    }
+
+    //a mapper picking lights like emitters does, e.g. by the light_pdf of a pass
+    pub fn with_emitters(
+        world: &'a HittableList,
+        emitters: Emitters<'a>,
+        maps: &'a PhotonMaps,
+        max_depth: i32,
+    ) -> Self {
+        Self {
+            world,
+            emitters,
+            maps,
+            max_depth,
+        }
+    }
+
+    pub fn ray_color(&self, r: &Ray) -> Vec3 {
+        let lights = self.emitters.lights;
+        let background = self.emitters.background;
+        let sampled_background = self.emitters.light_pdf[lights.objects.len()] > 0.0;
+        let mut color = Vec3::zero();
+        let mut beta = Vec3::ones();
+        let mut ray = *r;
+        //past the first diffuse hit, whose direct light was sampled
+        let mut gathering = false;
+        for _ in 0..self.max_depth {
+            let mut rec = match self.world.hit(&ray, 0.001, INF) {
+                Some(rec) => rec,
+                None => {
+                    if !gathering || !sampled_background {
+                        color += Vec3::elemul(beta, background);
+                    }
+                    break;
+                }
+            };
+            rec.set_footprint(&ray);
+            let r_in = rec.incident.unwrap_or(ray);
+            let mat = rec.mat_ptr;
+            //lights left out of the lights list are only found by the gather ray
+            if !gathering || light_index(lights, &r_in, rec.t).is_none() {
+                let emitted = mat.emitted(&r_in, &rec, rec.u, rec.v, &rec.p);
+                color += Vec3::elemul(beta, Vec3::elemul(emitted, rec.weight));
+            }
+            let s_rec = match mat.scatter(&r_in, &rec) {
+                Some(s_rec) => s_rec,
+                None => break,
+            };
+            let attenuation = Vec3::elemul(s_rec.attenuation, rec.weight);
+            let mut next = if s_rec.is_specular {
+                beta = Vec3::elemul(beta, attenuation);
+                s_rec.specular_ray
+            } else {
+                let surface = !mat.is_volumetric();
+                if surface && gathering {
+                    let global = &self.maps.global;
+                    let l = self
+                        .maps
+                        .estimate(global, GLOBAL_NEAREST, &r_in, &rec, attenuation);
+                    color += Vec3::elemul(beta, l);
+                    break;
+                }
+                if surface {
+                    let caustic = &self.maps.caustic;
+                    let l = self.direct(&r_in, &rec, attenuation)
+                        + self
+                            .maps
+                            .estimate(caustic, CAUSTIC_NEAREST, &r_in, &rec, attenuation);
+                    color += Vec3::elemul(beta, l);
+                    gathering = true;
+                }
+                let dir = s_rec.pdf_ptr.generate();
+                let pdf = s_rec.pdf_ptr.value(&dir);
+                let scattered = Ray::new(rec.p, dir, ray.tm);
+                let f = attenuation * mat.scattering_value(&r_in, &rec, &scattered);
+                if pdf.is_nan() || pdf <= 0.0 || f == Vec3::zero() {
+                    break;
+                }
+                beta = Vec3::elemul(beta, f) / pdf;
+                scattered
+            };
+            next.inherit_cone(&ray, rec.t);
+            ray = next;
+        }
+        color
+    }
+
+    //one light sample's light reflected back along r_in
+    fn direct(&self, r_in: &Ray, rec: &HitRecord, attenuation: Vec3) -> Vec3 {
+        match self.emitters.sample_incident(rec.p, r_in.tm) {
+            Some(li) => {
+                let scattered = Ray::new(rec.p, li.dir, r_in.tm);
+                let f = attenuation * rec.mat_ptr.scattering_value(r_in, rec, &scattered);
+                Vec3::elemul(f, li.weight())
+            }
+            None => Vec3::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::Integrator;
+    use crate::render::{mean_luminance, RenderSettings};
+    use crate::scene;
+
+    #[test]
+    fn test_matches_path_tracing_on_cornell() {
+        let scene = scene::select(6).unwrap();
+        let settings = RenderSettings {
+            image_width: 32,
+            image_height: 32,
+            //gathers reach past the last bounce, a deep limit leaves them little
+            max_depth: 10,
+            ..scene.settings
+        };
+        let mut cam = scene.camera;
+        cam.set_image_height(settings.image_height);
+        let mean = |integrator, samples_per_pixel| {
+            let settings = RenderSettings {
+                samples_per_pixel,
+                integrator,
+                ..settings
+            };
+            mean_luminance(&scene.world, &scene.lights, &cam, &settings, 5)
+        };
+        //the reference takes more samples, its own noise is not what is tested
+        let path = mean(Integrator::Path, 128);
+        let photon = mean(Integrator::Photon { photons: 20_000 }, 32);
+        assert!(path > 0.05);
+        assert!((photon - path).abs() < 0.05 * path, "{} {}", photon, path);
+    }
+
+    #[test]
+    fn test_nearest_matches_brute_force() {
+        seed_random(3);
+        let photons: Vec<Photon> = (0..2000)
+            .map(|_| Photon {
+                p: Vec3::random_in_unit_sphere() * 10.0,
+                dir: Vec3::new(0.0, -1.0, 0.0),
+                power: Vec3::ones(),
+            })
+            .collect();
+        let tree = KdTree::new(photons.clone());
+        assert_eq!(tree.len(), photons.len());
+        for _ in 0..50 {
+            let p = Vec3::random_in_unit_sphere() * 10.0;
+            let (near, dist2) = tree.nearest(p, 16, 4.0);
+            let mut brute: Vec<f64> = photons
+                .iter()
+                .map(|photon| (photon.p - p).squared_length())
+                .filter(|d| *d < 4.0)
+                .collect();
+            brute.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            brute.truncate(16);
+            let mut found: Vec<f64> = near.iter().map(|n| (n.p - p).squared_length()).collect();
+            found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(found, brute);
+            //a full gather shrinks the disk to its farthest photon
+            if brute.len() == 16 {
+                assert_eq!(dist2, brute[15]);
+            } else {
+                assert_eq!(dist2, 4.0);
+            }
+        }
+    }
+}
@@ -1,17 +1,53 @@
 use crate::vec3::Vec3;
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     pub orig: Vec3,
     pub dir: Vec3,
     pub tm: f64,
+    //wavelength in nm carried by spectral paths, 0 in RGB mode
+    pub lambda: f64,
+    //ray cone: footprint width at the origin and its growth per unit distance,
+    //both 0 for rays that do not filter textures
+    pub cone_width: f64,
+    pub cone_spread: f64,
 }
 
 impl Ray {
     pub fn new(orig: Vec3, dir: Vec3, tm: f64) -> Self {
-        Self { orig, dir, tm }
+        Self {
+            orig,
+            dir,
+            tm,
+            lambda: 0.0,
+            cone_width: 0.0,
+            cone_spread: 0.0,
+        }
+    }
+
+    pub fn new_with_lambda(orig: Vec3, dir: Vec3, tm: f64, lambda: f64) -> Self {
+        Self {
+            orig,
+            dir,
+            tm,
+            lambda,
+            cone_width: 0.0,
+            cone_spread: 0.0,
+        }
     }
 
     pub fn at(&self, t: f64) -> Vec3 {
         self.orig + self.dir * t
     }
+
+    //width of the ray cone where it reaches r.at(t)
+    pub fn footprint(&self, t: f64) -> f64 {
+        self.cone_width + self.cone_spread * t * self.dir.length()
+    }
+
+    //continues the cone of a parent ray from its hit at parameter t
+    pub fn inherit_cone(&mut self, parent: &Ray, t: f64) {
+        self.cone_width = parent.footprint(t);
+        self.cone_spread = parent.cone_spread;
+    }
 }
@@ -4,10 +4,10 @@ use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 use crate::{random_min_max, INF};
-use std::sync::Arc;
 
-pub struct XyRect {
-    mp: Arc<dyn Material>,
+#[derive(Clone)]
+pub struct XyRect<T: Material> {
+    mp: T,
     x0: f64,
     y0: f64,
     x1: f64,
@@ -15,8 +15,8 @@ pub struct XyRect {
     k: f64,
 }
 
-impl XyRect {
-    pub fn new(_x0: f64, _x1: f64, _y0: f64, _y1: f64, _k: f64, mat: Arc<dyn Material>) -> Self {
+impl<T: Material> XyRect<T> {
+    pub fn new(_x0: f64, _x1: f64, _y0: f64, _y1: f64, _k: f64, mat: T) -> Self {
         Self {
             mp: mat,
             x0: _x0,
@@ -28,8 +28,8 @@ impl XyRect {
     }
 }
 
-impl Hittable for XyRect {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl<T: 'static + Clone + Material + Send + Sync> Hittable for XyRect<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.orig.z) / r.dir.z;
         if t < t_min || t > t_max {
             return None;
@@ -44,9 +44,15 @@ impl Hittable for XyRect {
             normal: Vec3::zero(),
             t,
             front_face: false,
-            mat_ptr: self.mp.clone(),
+            mat_ptr: &self.mp,
             u: (x - self.x0) / (self.x1 - self.x0),
             v: (y - self.y0) / (self.y1 - self.y0),
+            dpdu: Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, self.y1 - self.y0, 0.0),
+            footprint: 0.0,
+            incident: None,
+            weight: Vec3::ones(),
+            object_id: 0,
         };
         let outward_normal = Vec3::new(0.0, 0.0, 1.0);
         rec.set_face_normal(r, &outward_normal);
@@ -60,10 +66,24 @@ impl Hittable for XyRect {
         );
         Some(output_box)
     }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+
+    fn sample_surface(&self) -> (Vec3, Vec3) {
+        let p = Vec3::new(
+            random_min_max(self.x0, self.x1),
+            random_min_max(self.y0, self.y1),
+            self.k,
+        );
+        (p, Vec3::new(0.0, 0.0, 1.0))
+    }
 }
 
-pub struct XzRect {
-    mp: Arc<dyn Material>,
+#[derive(Clone)]
+pub struct XzRect<T: Material> {
+    mp: T,
     x0: f64,
     z0: f64,
     x1: f64,
@@ -71,8 +91,8 @@ pub struct XzRect {
     k: f64,
 }
 
-impl XzRect {
-    pub fn new(_x0: f64, _x1: f64, _z0: f64, _z1: f64, _k: f64, mat: Arc<dyn Material>) -> Self {
+impl<T: Material> XzRect<T> {
+    pub fn new(_x0: f64, _x1: f64, _z0: f64, _z1: f64, _k: f64, mat: T) -> Self {
         Self {
             mp: mat,
             x0: _x0,
@@ -84,8 +104,8 @@ impl XzRect {
     }
 }
 
-impl Hittable for XzRect {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl<T: 'static + Clone + Material + Send + Sync> Hittable for XzRect<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.orig.y) / r.dir.y;
         if t < t_min || t > t_max {
             return None;
@@ -100,9 +120,15 @@ impl Hittable for XzRect {
             normal: Vec3::zero(),
             t,
             front_face: false,
-            mat_ptr: self.mp.clone(),
+            mat_ptr: &self.mp,
             u: (x - self.x0) / (self.x1 - self.x0),
             v: (z - self.z0) / (self.z1 - self.z0),
+            dpdu: Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, self.z1 - self.z0),
+            footprint: 0.0,
+            incident: None,
+            weight: Vec3::ones(),
+            object_id: 0,
         };
         let outward_normal = Vec3::new(0.0, 1.0, 0.0);
         rec.set_face_normal(r, &outward_normal);
@@ -136,10 +162,24 @@ impl Hittable for XzRect {
         );
         random_point - o.clone()
     }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
+
+    fn sample_surface(&self) -> (Vec3, Vec3) {
+        let p = Vec3::new(
+            random_min_max(self.x0, self.x1),
+            self.k,
+            random_min_max(self.z0, self.z1),
+        );
+        (p, Vec3::new(0.0, 1.0, 0.0))
+    }
 }
 
-pub struct YzRect {
-    mp: Arc<dyn Material>,
+#[derive(Clone)]
+pub struct YzRect<T: Material> {
+    mp: T,
     y0: f64,
     z0: f64,
     y1: f64,
@@ -147,8 +187,8 @@ pub struct YzRect {
     k: f64,
 }
 
-impl YzRect {
-    pub fn new(_y0: f64, _y1: f64, _z0: f64, _z1: f64, _k: f64, mat: Arc<dyn Material>) -> Self {
+impl<T: Material> YzRect<T> {
+    pub fn new(_y0: f64, _y1: f64, _z0: f64, _z1: f64, _k: f64, mat: T) -> Self {
         Self {
             mp: mat,
             y0: _y0,
@@ -160,8 +200,8 @@ impl YzRect {
     }
 }
 
-impl Hittable for YzRect {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl<T: 'static + Clone + Material + Send + Sync> Hittable for YzRect<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let t = (self.k - r.orig.x) / r.dir.x;
         if t < t_min || t > t_max {
             return None;
@@ -176,9 +216,15 @@ impl Hittable for YzRect {
             normal: Vec3::zero(),
             t,
             front_face: false,
-            mat_ptr: self.mp.clone(),
+            mat_ptr: &self.mp,
             u: (y - self.y0) / (self.y1 - self.y0),
             v: (z - self.z0) / (self.z1 - self.z0),
+            dpdu: Vec3::new(0.0, self.y1 - self.y0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, self.z1 - self.z0),
+            footprint: 0.0,
+            incident: None,
+            weight: Vec3::ones(),
+            object_id: 0,
         };
         let outward_normal = Vec3::new(1.0, 0.0, 0.0);
         rec.set_face_normal(r, &outward_normal);
@@ -192,4 +238,17 @@ impl Hittable for YzRect {
         );
         Some(output_box)
     }
+
+    fn area(&self) -> f64 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
+
+    fn sample_surface(&self) -> (Vec3, Vec3) {
+        let p = Vec3::new(
+            self.k,
+            random_min_max(self.y0, self.y1),
+            random_min_max(self.z0, self.z1),
+        );
+        (p, Vec3::new(1.0, 0.0, 0.0))
+    }
 }
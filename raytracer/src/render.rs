@@ -0,0 +1,443 @@
+use crate::bdpt::Bdpt;
+use crate::camera::Camera;
+use crate::checkpoint::Checkpoint;
+use crate::emitter::Emitters;
+use crate::film::{Features, Film, Splat};
+use crate::filter::Filter;
+use crate::hittable_list::HittableList;
+use crate::integrator::{
+    first_hit_features, lighting_passes, ray_color, ray_color_spectral, Integrator,
+};
+use crate::mlt;
+use crate::photon::{PhotonMapper, PhotonMaps};
+use crate::scheduler::{spiral_tiles, Tile, TileQueues, TILE_SIZE};
+use crate::spectrum::SampledWavelengths;
+use crate::vec3::Vec3;
+use crate::{random_0_1, seed_random};
+use indicatif::ProgressBar;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub background: Vec3,
+    //trace hero wavelengths instead of RGB
+    pub spectral: bool,
+    //adaptive sampling stops pixels whose noise estimate falls below this, 0 samples
+    //every pixel alike
+    pub noise_threshold: f64,
+    pub filter: Filter,
+    //also accumulate the emission, direct, indirect and per light passes of
+    //integrator::ray_color, RGB rendering only
+    pub split_lighting: bool,
+    //also gather the first hit features film::Features of the camera rays, for
+    //denoising and the surface AOVs
+    pub features: bool,
+    //bidirectional path tracing, photon mapping and Metropolis need RGB and no
+    //lighting passes
+    pub integrator: Integrator,
+}
+
+//samples per pixel a progressive pass adds
+pub const PASS_SAMPLES: u32 = 16;
+//most samples adaptive sampling gives one pixel, as a multiple of samples_per_pixel
+pub const ADAPTIVE_MAX_FACTOR: u32 = 8;
+//samples before a pixel's noise estimate is trusted
+pub const ADAPTIVE_MIN_SAMPLES: u32 = 32;
+
+//Renders all samples in one go, see render_pass for rendering in steps.
+pub fn render(
+    world: &HittableList,
+    lights: &HittableList,
+    cam: &Camera,
+    settings: &RenderSettings,
+) -> Film {
+    let mut state = Checkpoint::new(0, rand::random(), new_film(settings, lights));
+    let bar = progress_bar(settings, &state.film);
+    while render_pass(world, lights, cam, settings, &mut state, &bar) {}
+    bar.finish();
+    state.film
+}
+
+//mean luminance of the image rendered from the seed, for comparing integrators
+#[cfg(test)]
+pub(crate) fn mean_luminance(
+    world: &HittableList,
+    lights: &HittableList,
+    cam: &Camera,
+    settings: &RenderSettings,
+    seed: u64,
+) -> f64 {
+    let mut state = Checkpoint::new(0, seed, new_film(settings, lights));
+    let bar = progress_bar(settings, &state.film);
+    bar.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    while render_pass(world, lights, cam, settings, &mut state, &bar) {}
+    let pixels = state.film.pixels();
+    pixels.iter().map(|c| c.luminance()).sum::<f64>() / pixels.len() as f64
+}
+
+//an empty film of the image's size with the lighting passes the settings ask for
+pub fn new_film(settings: &RenderSettings, lights: &HittableList) -> Film {
+    Film::with_lighting(
+        settings.image_width,
+        settings.image_height,
+        film_lighting_passes(settings, lights),
+    )
+}
+
+fn film_lighting_passes(settings: &RenderSettings, lights: &HittableList) -> usize {
+    if settings.split_lighting && !settings.spectral {
+        lighting_passes(lights)
+    } else {
+        0
+    }
+}
+
+//total samples of the image, adaptive sampling shifts them between pixels
+pub(crate) fn sample_budget(settings: &RenderSettings) -> u64 {
+    settings.samples_per_pixel.max(0) as u64
+        * settings.image_width as u64
+        * settings.image_height as u64
+}
+
+//one tick per sample still to render, per mutation for Metropolis
+pub fn progress_bar(settings: &RenderSettings, film: &Film) -> ProgressBar {
+    let done = match settings.integrator {
+        Integrator::Mlt { .. } => film.light_paths,
+        _ => film.total_samples(),
+    };
+    ProgressBar::new(sample_budget(settings).saturating_sub(done))
+}
+
+//Samples every pixel takes in the next pass. Without a noise threshold all
+//pixels step towards samples_per_pixel, with one only the pixels still above
+//it are sampled, up to ADAPTIVE_MAX_FACTOR times as often, until the budget of
+//samples_per_pixel on average is spent.
+fn plan_pass(settings: &RenderSettings, film: &Film) -> Vec<u32> {
+    let spp = settings.samples_per_pixel.max(0) as u32;
+    let adaptive = settings.noise_threshold > 0.0;
+    let cap = if adaptive {
+        spp * ADAPTIVE_MAX_FACTOR
+    } else {
+        spp
+    };
+    let mut plan = vec![0; (film.width * film.height) as usize];
+    let mut active = 0;
+    for y in 0..film.height {
+        for x in 0..film.width {
+            let n = film.samples(x, y);
+            let error = film.error(x, y);
+            //NaN errors count as noisy
+            let noisy =
+                n < ADAPTIVE_MIN_SAMPLES || error.is_nan() || error > settings.noise_threshold;
+            if n < cap && (!adaptive || noisy) {
+                plan[(y * film.width + x) as usize] = (cap - n).min(PASS_SAMPLES);
+                active += 1;
+            }
+        }
+    }
+    let left = sample_budget(settings).saturating_sub(film.total_samples());
+    //the last pass spreads what is left evenly
+    let share = if active == 0 {
+        0
+    } else {
+        (left as f64 / active as f64).ceil() as u32
+    };
+    for p in plan.iter_mut() {
+        *p = (*p).min(share);
+    }
+    plan
+}
+
+//Adds up to PASS_SAMPLES samples per pixel to the checkpoint's film, rendering
+//tiles on one worker per core with work stealing. Returns false without
+//rendering once there is nothing left to sample. Metropolis has its own
+//passes, see mlt::render_pass.
+pub fn render_pass(
+    world: &HittableList,
+    lights: &HittableList,
+    cam: &Camera,
+    settings: &RenderSettings,
+    state: &mut Checkpoint,
+    bar: &ProgressBar,
+) -> bool {
+    if let Integrator::Mlt { .. } = settings.integrator {
+        return mlt::render_pass(world, lights, cam, settings, state, bar);
+    }
+    let plan = plan_pass(settings, &state.film);
+    if plan.iter().all(|n| *n == 0) {
+        return false;
+    }
+    let plan = Arc::new(plan);
+    let (seed, pass) = (state.seed, state.passes);
+    let n_workers = num_cpus::get().max(1);
+    let tiles = spiral_tiles(settings.image_width, settings.image_height, TILE_SIZE);
+    let n_tiles = tiles.len();
+    let regions: Vec<Tile> = tiles.iter().map(|t| filter_region(t, settings)).collect();
+    let queues = Arc::new(TileQueues::new(tiles, n_workers));
+    let world = Arc::new(world.clone());
+    let lights = Arc::new(lights.clone());
+    let light_pdf = Arc::new(match settings.integrator {
+        Integrator::Bdpt | Integrator::Photon { .. } => {
+            pass_light_pdf(&world, &lights, settings.background, seed, pass)
+        }
+        _ => Vec::new(),
+    });
+    //every pass shoots its own photons, their estimates average out over the passes
+    let photon_maps = Arc::new(match settings.integrator {
+        Integrator::Photon { photons } => {
+            //streams apart from the tiles
+            let maps = PhotonMaps::trace(
+                &world,
+                &lights,
+                &light_pdf,
+                settings.background,
+                settings.max_depth,
+                photons,
+                move |i| pass_seed(seed, pass, (1 << 63) | i),
+            );
+            Some(maps)
+        }
+        _ => None,
+    });
+    let (tx, rx) = channel();
+
+    let mut workers = Vec::with_capacity(n_workers);
+    for worker in 0..n_workers {
+        let tx = tx.clone();
+        let queues = queues.clone();
+        let world = world.clone();
+        let lights = lights.clone();
+        let light_pdf = light_pdf.clone();
+        let cam = *cam;
+        let settings = *settings;
+        let plan = plan.clone();
+        let photon_maps = photon_maps.clone();
+        workers.push(thread::spawn(move || {
+            while let Some((index, tile)) = queues.next(worker) {
+                seed_random(tile_seed(seed, pass, &tile));
+                let rendered = render_tile(
+                    &tile,
+                    &world,
+                    &lights,
+                    &light_pdf,
+                    &cam,
+                    &settings,
+                    &plan,
+                    photon_maps.as_ref().as_ref(),
+                );
+                if tx.send((index, rendered)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+    drop(tx);
+
+    //Filter margins overlap, merging in tile order keeps the sums independent of
+    //which tile finished first. A tile is merged once the tiles before it are,
+    //only those finishing ahead of an earlier one wait.
+    let mut pending = vec![None; n_tiles];
+    let mut next = 0;
+    for (index, rendered) in rx.iter() {
+        pending[index] = Some(rendered);
+        while let Some((tile_film, splats)) = pending.get_mut(next).and_then(Option::take) {
+            bar.inc(tile_film.total_samples());
+            state
+                .film
+                .merge(regions[next].x0, regions[next].y0, &tile_film);
+            for splat in splats.iter() {
+                state.film.add_splat(splat);
+            }
+            next += 1;
+        }
+    }
+    for worker in workers {
+        if let Err(e) = worker.join() {
+            std::panic::resume_unwind(e);
+        }
+    }
+    state.passes += 1;
+    true
+}
+
+//splitmix64 of the tile's pass and position, so a pass draws the same samples
+//whichever worker renders each tile
+fn tile_seed(seed: u64, pass: u32, tile: &Tile) -> u64 {
+    splitmix(seed ^ ((pass as u64) << 40) ^ ((tile.y0 as u64) << 20) ^ tile.x0 as u64)
+}
+
+//seed of one of a pass's streams of random numbers other than its tiles',
+//told apart by the high bits of stream
+pub(crate) fn pass_seed(seed: u64, pass: u32, stream: u64) -> u64 {
+    splitmix(seed ^ ((pass as u64) << 40) ^ stream)
+}
+
+//Probability of picking each light in a pass, estimated once from its own
+//stream so that every tile, photon worker and Metropolis chain of the pass
+//picks lights alike
+pub(crate) fn pass_light_pdf(
+    world: &HittableList,
+    lights: &HittableList,
+    background: Vec3,
+    seed: u64,
+    pass: u32,
+) -> Vec<f64> {
+    seed_random(pass_seed(seed, pass, 1 << 60));
+    Emitters::new(world, lights, background).light_pdf
+}
+
+fn splitmix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+//the pixels a tile's samples can reach through the filter
+pub fn filter_region(tile: &Tile, settings: &RenderSettings) -> Tile {
+    let margin = (settings.filter.radius - 0.5).ceil().max(0.0) as u32;
+    tile.expand(margin, settings.image_width, settings.image_height)
+}
+
+//Renders the samples the plan gives the tile's pixels into a film covering its
+//filter_region. Light paths of bdpt may reach any pixel, their splats are
+//returned in image coordinates. light_pdf is the pass's light selection and
+//photon_maps are its photons for the photon integrator.
+pub fn render_tile(
+    tile: &Tile,
+    world: &HittableList,
+    lights: &HittableList,
+    light_pdf: &[f64],
+    cam: &Camera,
+    settings: &RenderSettings,
+    plan: &[u32],
+    photon_maps: Option<&PhotonMaps>,
+) -> (Film, Vec<Splat>) {
+    let RenderSettings {
+        image_width,
+        image_height,
+        max_depth,
+        background,
+        spectral,
+        filter,
+        integrator,
+        features: gather_features,
+        ..
+    } = *settings;
+    let region = filter_region(tile, settings);
+    let n_passes = film_lighting_passes(settings, lights);
+    let mut film = Film::with_lighting(region.width(), region.height(), n_passes);
+    let mut passes = vec![Vec3::zero(); n_passes];
+    let mut splats = Vec::new();
+    let emitters = || Emitters::with_light_pdf(world, lights, background, light_pdf.to_vec());
+    let bdpt = match integrator {
+        Integrator::Bdpt => Some(Bdpt::with_emitters(world, cam, emitters(), max_depth)),
+        _ => None,
+    };
+    let photon_mapper =
+        photon_maps.map(|maps| PhotonMapper::with_emitters(world, emitters(), maps, max_depth));
+    for y in tile.y0..tile.y1 {
+        for x in tile.x0..tile.x1 {
+            for s in 0..plan[(y * image_width + x) as usize] {
+                let px = x as f64 + random_0_1();
+                let py = y as f64 + random_0_1();
+                let mut r = cam.get_ray(px / image_width as f64, 1.0 - py / image_height as f64);
+                let features = if gather_features {
+                    first_hit_features(&r, &background, world)
+                } else {
+                    Features::background(background)
+                };
+                let color = if spectral {
+                    let mut wavelengths = SampledWavelengths::sample_uniform(random_0_1());
+                    r.lambda = wavelengths.hero();
+                    ray_color_spectral(&r, &mut wavelengths, &background, world, lights, max_depth)
+                        .to_rgb(&wavelengths)
+                } else if let Some(bdpt) = &bdpt {
+                    film.add_light_paths(1);
+                    bdpt.sample(&r, image_width, image_height, &mut splats)
+                } else if let Some(photon_mapper) = &photon_mapper {
+                    photon_mapper.ray_color(&r)
+                } else {
+                    for pass in passes.iter_mut() {
+                        *pass = Vec3::zero();
+                    }
+                    ray_color(&r, &background, world, lights, max_depth, &mut passes)
+                };
+                film.add_sample(
+                    px - region.x0 as f64,
+                    py - region.y0 as f64,
+                    color,
+                    &passes,
+                    &features,
+                    &filter,
+                );
+            }
+        }
+    }
+    (film, splats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+    use indicatif::ProgressDrawTarget;
+
+    #[test]
+    fn test_adaptive_skips_converged_pixels() {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Vec3::zero(),
+            1.0,
+            Lambertian::new(SolidColor::new_with_vec(Vec3::new(0.5, 0.5, 0.5))),
+        )));
+        world.add(Arc::new(Sphere::new(
+            Vec3::new(0.0, -101.0, 0.0),
+            100.0,
+            Lambertian::new(SolidColor::new_with_vec(Vec3::new(0.5, 0.5, 0.5))),
+        )));
+        let settings = RenderSettings {
+            image_width: 40,
+            image_height: 30,
+            samples_per_pixel: 64,
+            max_depth: 5,
+            background: Vec3::new(0.7, 0.8, 1.0),
+            spectral: false,
+            noise_threshold: 0.005,
+            filter: Filter::pixel_box(),
+            split_lighting: false,
+            features: false,
+            integrator: Integrator::Path,
+        };
+        let mut cam = Camera::new(
+            Vec3::new(0.0, 0.0, 4.0),
+            Vec3::zero(),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            4.0 / 3.0,
+            0.0,
+            4.0,
+            0.0,
+            1.0,
+        );
+        cam.set_image_height(settings.image_height);
+        let lights = HittableList::new();
+        let mut state = Checkpoint::new(0, 3, Film::new(40, 30));
+        let bar = progress_bar(&settings, &state.film);
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+        while render_pass(&world, &lights, &cam, &settings, &mut state, &bar) {}
+
+        //the plain sky converges at once, the shaded spheres take the rest
+        assert_eq!(state.film.samples(0, 0), ADAPTIVE_MIN_SAMPLES);
+        assert!(state.film.samples(20, 15) > 64);
+        assert!(state.film.total_samples() <= 64 * 40 * 30 + 40 * 30);
+    }
+}
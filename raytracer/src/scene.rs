@@ -1,15 +1,361 @@
-use crate::bvh_static::BvhNode;
-use crate::cornell_box_static::Box;
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::cornell_box::Box;
+use crate::error::Result;
+use crate::filter::Filter;
+use crate::heightfield::Heightfield;
+use crate::hittable::{ConstantMedium, FlipFace, Hittable, RotateY, Translate};
 use crate::hittable_list::HittableList;
-use crate::hittable_static::{ConstantMedium, FlipFace, Hittable, RotateY, Translate};
-use crate::material_static::{Dielectric, DiffuseLight, Isotropic, Lambertian, Metal};
-use crate::rectangle_static::{XyRect, XzRect, YzRect};
-use crate::sphere_static::{MovingSphere, Sphere};
-use crate::texture_static::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor};
+use crate::integrator::Integrator;
+use crate::material::{
+    Clearcoat, Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Metal,
+    MixMaterial, OrenNayar, Sheen,
+};
+use crate::medium::{Atmosphere, EmissiveMedium, HeterogeneousMedium, NoiseDensity, Subsurface};
+use crate::perlin::Perlin;
+use crate::rectangle::{XyRect, XzRect, YzRect};
+use crate::render::RenderSettings;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor};
+use crate::thin_film::{FilmDielectric, FilmMetal, ThinFilm};
 use crate::vec3::Vec3;
 use crate::{random_0_1, random_int, random_min_max};
 use std::sync::Arc;
 
+//everything needed to render one of the scenes below
+pub struct Scene {
+    pub world: HittableList,
+    pub lights: HittableList,
+    pub camera: Camera,
+    pub settings: RenderSettings,
+}
+
+//scene table of the command line renderer, unknown indices give my_scene
+pub fn select(index: u32) -> Result<Scene> {
+    //image
+    let mut aspect_ratio = 16.0 / 9.0;
+    let mut image_width: u32 = 400;
+    let mut samples_per_pixel = 100;
+    let max_depth = 40;
+
+    //world
+    let mut world = HittableList::new();
+    let mut lookfrom = Vec3::zero();
+    let mut lookat = Vec3::zero();
+    let mut vfov = 40.0;
+    let mut aperture = 0.0;
+    let mut background = Vec3::zero();
+    let mut lights = HittableList::new();
+    let mut spectral = false;
+    match index {
+        1 => {
+            world = random_scene();
+            background = Vec3::new(0.7, 0.8, 1.0);
+            lookfrom = Vec3::new(13.0, 2.0, 3.0);
+            lookat = Vec3::zero();
+            vfov = 20.0;
+            aperture = 0.1;
+        }
+        2 => {
+            world = two_spheres();
+            background = Vec3::new(0.7, 0.8, 1.0);
+            lookfrom = Vec3::new(13.0, 2.0, 3.0);
+            lookat = Vec3::zero();
+            vfov = 20.0;
+        }
+        3 => {
+            world = two_perlin_spheres();
+            background = Vec3::new(0.7, 0.8, 1.0);
+            lookfrom = Vec3::new(13.0, 2.0, 3.0);
+            lookat = Vec3::zero();
+            vfov = 20.0;
+        }
+        4 => {
+            world = earth()?;
+            lookfrom = Vec3::new(13.0, 2.0, 3.0);
+            background = Vec3::new(0.7, 0.8, 1.0);
+            vfov = 20.0;
+        }
+        5 => {
+            world = simple_light();
+            samples_per_pixel = 400;
+            background = Vec3::zero();
+            lookfrom = Vec3::new(26.0, 3.0, 6.0);
+            lookat = Vec3::new(0.0, 2.0, 0.0);
+            vfov = 20.0;
+        }
+        6 => {
+            world = cornell_box();
+            lights.add(Arc::new(XzRect::new(
+                213.0,
+                343.0,
+                227.0,
+                332.0,
+                554.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            lights.add(Arc::new(Sphere::new(
+                Vec3::new(190.0, 90.0, 190.0),
+                90.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 1.0;
+            image_width = 600;
+            samples_per_pixel = 1000;
+            background = Vec3::zero();
+            lookfrom = Vec3::new(278.0, 278.0, -800.0);
+            lookat = Vec3::new(278.0, 278.0, 0.0);
+            vfov = 40.0;
+        }
+        7 => {
+            world = cornell_smoke();
+            lights.add(Arc::new(XzRect::new(
+                113.0,
+                443.0,
+                127.0,
+                432.0,
+                554.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 1.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            lookfrom = Vec3::new(278.0, 278.0, -800.0);
+            lookat = Vec3::new(278.0, 278.0, 0.0);
+            vfov = 40.0;
+        }
+        8 => {
+            world = final_scene()?;
+            lights.add(Arc::new(XzRect::new(
+                123.0,
+                423.0,
+                147.0,
+                412.0,
+                554.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 1.0;
+            image_width = 800;
+            samples_per_pixel = 1000; //10,000 is too big
+            background = Vec3::zero();
+            lookfrom = Vec3::new(578.0, 0.0, -800.0);
+            lookat = Vec3::new(378.0, 200.0, 0.0);
+            vfov = 40.0;
+        }
+        9 => {
+            world = terrain()?;
+            lights.add(Arc::new(Sphere::new(
+                Vec3::new(-2000.0, 3000.0, 2500.0),
+                500.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 2.0;
+            image_width = 800;
+            samples_per_pixel = 100;
+            background = Vec3::new(0.7, 0.8, 1.0);
+            lookfrom = Vec3::new(0.0, 450.0, -900.0);
+            lookat = Vec3::new(0.0, 100.0, 0.0);
+            vfov = 40.0;
+        }
+        10 => {
+            world = cornell_cloud();
+            lights.add(Arc::new(XzRect::new(
+                113.0,
+                443.0,
+                127.0,
+                432.0,
+                554.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 1.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::zero();
+            lookfrom = Vec3::new(278.0, 278.0, -800.0);
+            lookat = Vec3::new(278.0, 278.0, 0.0);
+            vfov = 40.0;
+        }
+        11 => {
+            world = cornell_fire();
+            lights.add(Arc::new(XzRect::new(
+                113.0,
+                443.0,
+                127.0,
+                432.0,
+                554.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 1.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::zero();
+            lookfrom = Vec3::new(278.0, 278.0, -800.0);
+            lookat = Vec3::new(278.0, 278.0, 0.0);
+            vfov = 40.0;
+        }
+        12 => {
+            world = prisms();
+            lights.add(Arc::new(XzRect::new(
+                -1.0,
+                1.0,
+                -1.0,
+                1.0,
+                12.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            spectral = true;
+            aspect_ratio = 16.0 / 9.0;
+            image_width = 600;
+            samples_per_pixel = 500;
+            background = Vec3::zero();
+            lookfrom = Vec3::new(0.0, 6.0, -12.0);
+            lookat = Vec3::new(0.0, 0.5, 0.0);
+            vfov = 30.0;
+        }
+        13 => {
+            world = bubbles();
+            lights.add(Arc::new(Sphere::new(
+                Vec3::new(-20.0, 40.0, -20.0),
+                8.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 16.0 / 9.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::new(0.6, 0.7, 0.9);
+            lookfrom = Vec3::new(0.0, 3.0, -10.0);
+            lookat = Vec3::new(0.0, 1.0, 0.0);
+            vfov = 35.0;
+        }
+        14 => {
+            world = cornell_subsurface();
+            lights.add(Arc::new(XzRect::new(
+                113.0,
+                443.0,
+                127.0,
+                432.0,
+                554.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 1.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::zero();
+            lookfrom = Vec3::new(278.0, 278.0, -800.0);
+            lookat = Vec3::new(278.0, 278.0, 0.0);
+            vfov = 40.0;
+        }
+        15 => {
+            world = clearcoat();
+            lights.add(Arc::new(XzRect::new(
+                -3.0,
+                3.0,
+                -3.0,
+                3.0,
+                8.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 16.0 / 9.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::new(0.05, 0.05, 0.08);
+            lookfrom = Vec3::new(0.0, 3.0, -9.0);
+            lookat = Vec3::new(0.0, 1.0, 0.0);
+            vfov = 35.0;
+        }
+        16 => {
+            world = mixed_materials();
+            lights.add(Arc::new(XzRect::new(
+                -3.0,
+                3.0,
+                -3.0,
+                3.0,
+                8.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 16.0 / 9.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::new(0.05, 0.05, 0.08);
+            lookfrom = Vec3::new(0.0, 3.0, -9.0);
+            lookat = Vec3::new(0.0, 1.0, 0.0);
+            vfov = 30.0;
+        }
+        17 => {
+            world = rough_diffuse();
+            lights.add(Arc::new(XzRect::new(
+                -3.0,
+                3.0,
+                -12.0,
+                -8.0,
+                8.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::zero())),
+            )));
+            aspect_ratio = 16.0 / 9.0;
+            image_width = 600;
+            samples_per_pixel = 200;
+            background = Vec3::new(0.02, 0.02, 0.03);
+            lookfrom = Vec3::new(0.0, 2.5, -10.0);
+            lookat = Vec3::new(0.0, 1.0, 0.0);
+            vfov = 35.0;
+        }
+        _ => {
+            world = my_scene()?;
+            lights.add(Arc::new(XzRect::new(
+                213.0,
+                343.0,
+                227.0,
+                332.0,
+                354.0,
+                Lambertian::new(SolidColor::new_with_vec(Vec3::new(7.0, 7.0, 7.0))),
+            )));
+            aspect_ratio = 2.0;
+            image_width = 800;
+            samples_per_pixel = 80;
+            background = Vec3::new(0.7, 0.8, 1.0);
+            // background = Vec3::zero();
+            lookfrom = Vec3::new(478.0, 178.0, -800.0);
+            lookat = Vec3::new(378.0, 278.0, 0.0);
+            vfov = 40.0;
+        }
+    }
+
+    //camera
+    let image_height: u32 = (image_width as f64 / aspect_ratio) as u32;
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let mut camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+    camera.set_image_height(image_height);
+    Ok(Scene {
+        world,
+        lights,
+        camera,
+        settings: RenderSettings {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            max_depth,
+            background,
+            spectral,
+            noise_threshold: 0.0,
+            filter: Filter::pixel_box(),
+            split_lighting: false,
+            features: false,
+            integrator: Integrator::Path,
+        },
+    })
+}
+
 pub fn random_scene() -> HittableList {
     let mut world = HittableList::new();
     let checker = CheckerTexture::new(
@@ -113,12 +459,12 @@ pub fn two_perlin_spheres() -> HittableList {
     objects
 }
 
-pub fn earth() -> HittableList {
+pub fn earth() -> Result<HittableList> {
     let mut objects = HittableList::new();
-    let earth_texture = ImageTexture::new("jpg/earthmap.jpg");
+    let earth_texture = ImageTexture::new("jpg/earthmap.jpg")?;
     let earth_surface = Lambertian::new(earth_texture);
     objects.add(Arc::new(Sphere::new(Vec3::zero(), 2.0, earth_surface)));
-    objects
+    Ok(objects)
 }
 
 pub fn simple_light() -> HittableList {
@@ -210,9 +556,9 @@ pub fn cornell_smoke() -> HittableList {
     let light = DiffuseLight::new(SolidColor::new_with_col(7.0, 7.0, 7.0));
     objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
     objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
-    objects.add(Arc::new(XzRect::new(
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
         113.0, 443.0, 127.0, 432.0, 554.0, light,
-    )));
+    ))));
     objects.add(Arc::new(XzRect::new(
         0.0,
         555.0,
@@ -256,7 +602,155 @@ pub fn cornell_smoke() -> HittableList {
     objects
 }
 
-pub fn final_scene() -> HittableList {
+pub fn cornell_cloud() -> HittableList {
+    let mut objects = HittableList::new();
+    let red = Lambertian::new(SolidColor::new_with_col(0.65, 0.05, 0.05));
+    let white = Lambertian::new(SolidColor::new_with_col(0.73, 0.73, 0.73));
+    let green = Lambertian::new(SolidColor::new_with_col(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(SolidColor::new_with_col(7.0, 7.0, 7.0));
+    objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        113.0, 443.0, 127.0, 432.0, 554.0, light,
+    ))));
+    objects.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    objects.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    objects.add(Arc::new(XyRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    let boundary = Sphere::new(Vec3::new(278.0, 278.0, 278.0), 180.0, white.clone());
+    objects.add(Arc::new(HeterogeneousMedium::new(
+        boundary,
+        NoiseDensity::new(0.01, 0.3),
+        HenyeyGreenstein::new(SolidColor::new_with_col(0.9, 0.9, 0.9), 0.6),
+    )));
+    objects
+}
+
+pub fn cornell_subsurface() -> HittableList {
+    let mut objects = HittableList::new();
+    let red = Lambertian::new(SolidColor::new_with_col(0.65, 0.05, 0.05));
+    let white = Lambertian::new(SolidColor::new_with_col(0.73, 0.73, 0.73));
+    let green = Lambertian::new(SolidColor::new_with_col(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(SolidColor::new_with_col(7.0, 7.0, 7.0));
+    objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        113.0, 443.0, 127.0, 432.0, 554.0, light,
+    ))));
+    objects.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    objects.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    objects.add(Arc::new(XyRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    //wax behind a glossy interface
+    objects.add(Arc::new(Subsurface::new(
+        Sphere::new(Vec3::new(380.0, 120.0, 300.0), 120.0, Dielectric::new(1.4)),
+        Vec3::new(40.0, 20.0, 8.0),
+        Vec3::new(0.99, 0.95, 0.85),
+        0.0,
+    )));
+    //jade with an index matched boundary
+    let jade = Box::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(150.0, 260.0, 150.0),
+        Dielectric::new(1.0),
+    );
+    objects.add(Arc::new(Subsurface::new(
+        Translate::new(RotateY::new(jade, 20.0), Vec3::new(110.0, 0.0, 200.0)),
+        Vec3::new(15.0, 40.0, 20.0),
+        Vec3::new(0.8, 0.98, 0.85),
+        0.3,
+    )));
+    objects
+}
+
+pub fn cornell_fire() -> HittableList {
+    let mut objects = HittableList::new();
+    let red = Lambertian::new(SolidColor::new_with_col(0.65, 0.05, 0.05));
+    let white = Lambertian::new(SolidColor::new_with_col(0.73, 0.73, 0.73));
+    let green = Lambertian::new(SolidColor::new_with_col(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(SolidColor::new_with_col(2.0, 2.0, 2.0));
+    objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)));
+    objects.add(Arc::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        113.0, 443.0, 127.0, 432.0, 554.0, light,
+    ))));
+    objects.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        0.0,
+        white.clone(),
+    )));
+    objects.add(Arc::new(XzRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    objects.add(Arc::new(XyRect::new(
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        white.clone(),
+    )));
+    let boundary = Sphere::new(Vec3::new(278.0, 200.0, 278.0), 150.0, white.clone());
+    objects.add(Arc::new(EmissiveMedium::new(
+        boundary,
+        NoiseDensity::new(0.01, 0.2),
+        NoiseDensity::new(0.008, 3000.0),
+        20.0,
+        0.3,
+        Isotropic::new(SolidColor::new_with_vec(Vec3::ones())),
+    )));
+    objects
+}
+
+pub fn final_scene() -> Result<HittableList> {
     let mut boxes1 = HittableList::new();
     let ground = Lambertian::new(SolidColor::new_with_col(0.48, 0.83, 0.53));
     let boxes_per_side = 20;
@@ -278,11 +772,11 @@ pub fn final_scene() -> HittableList {
     }
 
     let mut objects = HittableList::new();
-    objects.add(Arc::new(BvhNode::new_with_list(&mut boxes1, 0.0, 1.0)));
+    objects.add(Arc::new(BvhNode::new_with_list(&mut boxes1, 0.0, 1.0)?));
     let light = DiffuseLight::new(SolidColor::new_with_col(7.0, 7.0, 7.0));
-    objects.add(Arc::new(XzRect::new(
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
         123.0, 423.0, 147.0, 412.0, 554.0, light,
-    )));
+    ))));
     let center1 = Vec3::new(400.0, 400.0, 200.0);
     let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
     let moving_sphere_material = Lambertian::new(SolidColor::new_with_col(0.7, 0.3, 0.1));
@@ -304,20 +798,19 @@ pub fn final_scene() -> HittableList {
         50.0,
         Metal::new(Vec3::new(0.8, 0.8, 0.9), 1.0),
     )));
-    let mut boundary = Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, Dielectric::new(1.5));
+    let boundary = Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, Dielectric::new(1.5));
     objects.add(Arc::new(boundary.clone()));
     objects.add(Arc::new(ConstantMedium::new(
         boundary,
         0.2,
         Isotropic::new(SolidColor::new_with_col(0.2, 0.4, 0.9)),
     )));
-    boundary = Sphere::new(Vec3::zero(), 5000.0, Dielectric::new(1.5));
-    objects.add(Arc::new(ConstantMedium::new(
-        boundary,
+    objects.add(Arc::new(Atmosphere::new(
         0.0001,
+        1000.0,
         Isotropic::new(SolidColor::new_with_vec(Vec3::ones())),
     )));
-    let emat = Lambertian::new(ImageTexture::new("jpg/earthmap.jpg"));
+    let emat = Lambertian::new(ImageTexture::new("jpg/earthmap.jpg")?);
     objects.add(Arc::new(Sphere::new(
         Vec3::new(400.0, 200.0, 400.0),
         100.0,
@@ -339,17 +832,17 @@ pub fn final_scene() -> HittableList {
             white.clone(),
         )));
     }
-    let arc_bvh = BvhNode::new_with_list(&mut boxes2, 0.0, 1.0);
+    let arc_bvh = BvhNode::new_with_list(&mut boxes2, 0.0, 1.0)?;
     let arc_rotate = RotateY::new(arc_bvh, 15.0);
     objects.add(Arc::new(Translate::new(
         arc_rotate,
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 
-    objects
+    Ok(objects)
 }
 
-pub fn my_scene() -> HittableList {
+pub fn my_scene() -> Result<HittableList> {
     let mut objects = HittableList::new();
     let sphereR = 40.0;
     let basepairR = 4.0;
@@ -367,7 +860,7 @@ pub fn my_scene() -> HittableList {
         -1000.0,
         1000.0,
         600.0,
-        Lambertian::new(ImageTexture::new("jpg/blue_dna1.jpg")),
+        Lambertian::new(ImageTexture::new("jpg/blue_dna1.jpg")?),
     )));
     objects.add(Arc::new(FlipFace::new(XzRect::new(
         413.0,
@@ -442,9 +935,230 @@ pub fn my_scene() -> HittableList {
         }
         t += 0.5;
     }
+    Ok(objects)
+}
+
+pub fn prisms() -> HittableList {
+    let mut objects = HittableList::new();
+    let white = Lambertian::new(SolidColor::new_with_col(0.8, 0.8, 0.8));
+    objects.add(Arc::new(XzRect::new(-20.0, 20.0, -20.0, 20.0, 0.0, white)));
+    let light = DiffuseLight::new(SolidColor::new_with_col(60.0, 60.0, 60.0));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        -1.0, 1.0, -1.0, 1.0, 12.0, light,
+    ))));
+    let prism = Box::new(
+        Vec3::new(-1.0, 0.0, -1.0),
+        Vec3::new(1.0, 2.0, 1.0),
+        Dielectric::new_with_cauchy(1.6, 0.05),
+    );
+    objects.add(Arc::new(Translate::new(
+        RotateY::new(prism, 35.0),
+        Vec3::new(-2.0, 0.0, 1.0),
+    )));
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(2.0, 1.2, 0.0),
+        1.2,
+        Dielectric::diamond(),
+    )));
+    objects
+}
+
+pub fn bubbles() -> HittableList {
+    let mut objects = HittableList::new();
+    let ground = Lambertian::new(SolidColor::new_with_col(0.05, 0.05, 0.06));
+    objects.add(Arc::new(XzRect::new(-50.0, 50.0, -50.0, 50.0, 0.0, ground)));
+    //oil slick on a puddle
+    let oil = FilmDielectric::new(
+        Dielectric::new(1.33),
+        ThinFilm::new(NoiseTexture::new(2.0), 150.0, 600.0, 1.47),
+    );
+    objects.add(Arc::new(XzRect::new(-6.0, 6.0, -4.0, 2.0, 0.01, oil)));
+    for i in 0..5 {
+        let soap = FilmDielectric::new(
+            Dielectric::new(1.0),
+            ThinFilm::new(NoiseTexture::new(1.0 + i as f64), 200.0, 900.0, 1.33),
+        );
+        objects.add(Arc::new(Sphere::new(
+            Vec3::new(-3.0 + 1.5 * i as f64, 1.2 + 0.4 * (i % 2) as f64, 0.0),
+            0.6,
+            soap,
+        )));
+    }
+    let coated = FilmMetal::new(
+        Metal::new(Vec3::new(0.8, 0.8, 0.8), 0.0),
+        ThinFilm::new(SolidColor::new_with_col(1.0, 1.0, 1.0), 350.0, 350.0, 1.5),
+    );
+    objects.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 3.0), 1.0, coated)));
+    let sun = DiffuseLight::new(SolidColor::new_with_col(15.0, 15.0, 15.0));
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(-20.0, 40.0, -20.0),
+        8.0,
+        sun,
+    )));
+    objects
+}
+
+pub fn clearcoat() -> HittableList {
+    let mut objects = HittableList::new();
+    let checker = CheckerTexture::new(
+        SolidColor::new_with_col(0.2, 0.2, 0.2),
+        SolidColor::new_with_col(0.8, 0.8, 0.8),
+    );
+    objects.add(Arc::new(XzRect::new(
+        -50.0,
+        50.0,
+        -50.0,
+        50.0,
+        0.0,
+        Lambertian::new(checker),
+    )));
+    let light = DiffuseLight::new(SolidColor::new_with_col(10.0, 10.0, 10.0));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    ))));
+    let car_paint = Clearcoat::new(
+        Lambertian::new(SolidColor::new_with_col(0.6, 0.02, 0.02)),
+        1.5,
+        1.0,
+        Vec3::ones(),
+    );
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(-2.5, 1.0, 0.0),
+        1.0,
+        car_paint,
+    )));
+    let metallic_paint = Clearcoat::new(
+        Metal::new(Vec3::new(0.2, 0.3, 0.7), 0.4),
+        1.5,
+        1.0,
+        Vec3::ones(),
+    );
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        metallic_paint,
+    )));
+    let varnished_wood = Clearcoat::new_with_fuzz(
+        Lambertian::new(NoiseTexture::new(4.0)),
+        1.5,
+        1.0,
+        Vec3::new(0.85, 0.6, 0.3),
+        0.05,
+    );
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(2.5, 1.0, 0.0),
+        1.0,
+        varnished_wood,
+    )));
     objects
 }
 
+pub fn mixed_materials() -> HittableList {
+    let mut objects = HittableList::new();
+    objects.add(Arc::new(XzRect::new(
+        -50.0,
+        50.0,
+        -50.0,
+        50.0,
+        0.0,
+        Lambertian::new(SolidColor::new_with_col(0.5, 0.5, 0.5)),
+    )));
+    let light = DiffuseLight::new(SolidColor::new_with_col(10.0, 10.0, 10.0));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        -3.0, 3.0, -3.0, 3.0, 8.0, light,
+    ))));
+    //rust eating into polished steel
+    let rusty = MixMaterial::new(
+        Metal::new(Vec3::new(0.8, 0.8, 0.85), 0.05),
+        Lambertian::new(SolidColor::new_with_col(0.45, 0.2, 0.08)),
+        NoiseTexture::new(3.0),
+    );
+    objects.add(Arc::new(Sphere::new(Vec3::new(-1.3, 1.0, 0.0), 1.0, rusty)));
+    //dirt over coated paint
+    let dirty = MixMaterial::new(
+        Clearcoat::new(
+            Lambertian::new(SolidColor::new_with_col(0.05, 0.2, 0.6)),
+            1.5,
+            1.0,
+            Vec3::ones(),
+        ),
+        Lambertian::new(SolidColor::new_with_col(0.3, 0.25, 0.2)),
+        CheckerTexture::new(
+            SolidColor::new_with_col(0.0, 0.0, 0.0),
+            SolidColor::new_with_col(0.7, 0.7, 0.7),
+        ),
+    );
+    objects.add(Arc::new(Sphere::new(Vec3::new(1.3, 1.0, 0.0), 1.0, dirty)));
+    objects
+}
+
+pub fn rough_diffuse() -> HittableList {
+    let mut objects = HittableList::new();
+    objects.add(Arc::new(XzRect::new(
+        -50.0,
+        50.0,
+        -50.0,
+        50.0,
+        0.0,
+        Lambertian::new(SolidColor::new_with_col(0.4, 0.4, 0.4)),
+    )));
+    //light above and behind the camera shows the flat back scattering of rough surfaces
+    let light = DiffuseLight::new(SolidColor::new_with_col(15.0, 15.0, 15.0));
+    objects.add(Arc::new(FlipFace::new(XzRect::new(
+        -3.0, 3.0, -12.0, -8.0, 8.0, light,
+    ))));
+    let clay = SolidColor::new_with_col(0.7, 0.45, 0.3);
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(-3.3, 1.0, 0.0),
+        1.0,
+        Lambertian::new(clay.clone()),
+    )));
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(-1.1, 1.0, 0.0),
+        1.0,
+        OrenNayar::new(clay, SolidColor::new_with_col(0.5, 0.5, 0.5)),
+    )));
+    //moon dust, roughness varying over the surface
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(1.1, 1.0, 0.0),
+        1.0,
+        OrenNayar::new(
+            SolidColor::new_with_col(0.6, 0.6, 0.6),
+            NoiseTexture::new(2.0),
+        ),
+    )));
+    let velvet = MixMaterial::new(
+        Lambertian::new(SolidColor::new_with_col(0.3, 0.02, 0.05)),
+        Sheen::new(SolidColor::new_with_col(1.0, 0.4, 0.5), 0.3),
+        SolidColor::new_with_col(0.5, 0.5, 0.5),
+    );
+    objects.add(Arc::new(Sphere::new(Vec3::new(3.3, 1.0, 0.0), 1.0, velvet)));
+    objects
+}
+
+pub fn terrain() -> Result<HittableList> {
+    let mut objects = HittableList::new();
+    let ground = Lambertian::new(SolidColor::new_with_col(0.45, 0.38, 0.28));
+    objects.add(Arc::new(Heightfield::new_with_perlin(
+        &Perlin::new(),
+        512,
+        -1000.0,
+        1000.0,
+        -1000.0,
+        1000.0,
+        0.004,
+        300.0,
+        ground,
+    )?));
+    let sun = DiffuseLight::new(SolidColor::new_with_col(20.0, 18.0, 15.0));
+    objects.add(Arc::new(Sphere::new(
+        Vec3::new(-2000.0, 3000.0, 2500.0),
+        500.0,
+        sun,
+    )));
+    Ok(objects)
+}
+
 fn generate_color() -> Vec<Metal> {
     let mut v: Vec<Metal> = vec![];
     let fuzz = 0.8;
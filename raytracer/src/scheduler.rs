@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+pub const TILE_SIZE: u32 = 32;
+
+//pixels [x0, x1) x [y0, y1), y counted from the top row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    //grown by margin pixels on every side, clipped to the image
+    pub fn expand(&self, margin: u32, width: u32, height: u32) -> Tile {
+        Tile {
+            x0: self.x0.saturating_sub(margin),
+            y0: self.y0.saturating_sub(margin),
+            x1: (self.x1 + margin).min(width),
+            y1: (self.y1 + margin).min(height),
+        }
+    }
+}
+
+//tiles covering the image in a square spiral out of the centre, so the
+//interesting part of most scenes finishes first
+pub fn spiral_tiles(width: u32, height: u32, size: u32) -> Vec<Tile> {
+    let nx = (width as f64 / size as f64).ceil() as i64;
+    let ny = (height as f64 / size as f64).ceil() as i64;
+    let total = (nx * ny) as usize;
+    let mut tiles = Vec::with_capacity(total);
+    let (mut x, mut y) = ((nx - 1) / 2, (ny - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 0;
+    while tiles.len() < total {
+        let (dx, dy) = directions[leg % 4];
+        //legs grow by one every second turn: 1, 1, 2, 2, 3, 3, ...
+        for _ in 0..leg / 2 + 1 {
+            if x >= 0 && x < nx && y >= 0 && y < ny {
+                let (x0, y0) = (x as u32 * size, y as u32 * size);
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + size).min(width),
+                    y1: (y0 + size).min(height),
+                });
+            }
+            x += dx;
+            y += dy;
+        }
+        leg += 1;
+    }
+    tiles
+}
+
+//One deque per worker, dealt round robin so every queue starts near the centre.
+//Workers take from the front of their own queue and, once it runs dry, steal
+//from the back of the longest other one. Tiles come with their index in the
+//order they were given.
+pub struct TileQueues {
+    queues: Vec<Mutex<VecDeque<(usize, Tile)>>>,
+}
+
+impl TileQueues {
+    pub fn new(tiles: Vec<Tile>, n_workers: usize) -> Self {
+        let mut queues = vec![VecDeque::new(); n_workers.max(1)];
+        let n = queues.len();
+        for (i, tile) in tiles.into_iter().enumerate() {
+            queues[i % n].push_back((i, tile));
+        }
+        Self {
+            queues: queues.into_iter().map(Mutex::new).collect(),
+        }
+    }
+
+    pub fn next(&self, worker: usize) -> Option<(usize, Tile)> {
+        if let Some(tile) = self.queues[worker].lock().unwrap().pop_front() {
+            return Some(tile);
+        }
+        loop {
+            let (victim, len) = (0..self.queues.len())
+                .filter(|i| *i != worker)
+                .map(|i| (i, self.queues[i].lock().unwrap().len()))
+                .max_by_key(|(_, len)| *len)?;
+            if len == 0 {
+                return None;
+            }
+            //another thief may have emptied it in between, then look again
+            if let Some(tile) = self.queues[victim].lock().unwrap().pop_back() {
+                return Some(tile);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spiral_covers_image() {
+        let (width, height) = (200, 90);
+        let tiles = spiral_tiles(width, height, TILE_SIZE);
+        assert_eq!(tiles.len(), 7 * 3);
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in tiles.iter() {
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    covered[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|c| *c == 1));
+        //the first tile holds the image centre
+        let first = tiles[0];
+        assert!(first.x0 <= width / 2 && width / 2 < first.x1);
+        assert!(first.y0 <= height / 2 && height / 2 < first.y1);
+    }
+
+    #[test]
+    fn test_idle_worker_steals() {
+        let tiles = spiral_tiles(128, 128, TILE_SIZE);
+        let queues = TileQueues::new(tiles, 4);
+        //worker 0 drains its own four tiles, then everybody else's
+        let mut taken = 0;
+        while queues.next(0).is_some() {
+            taken += 1;
+        }
+        assert_eq!(taken, 16);
+        assert!(queues.next(3).is_none());
+    }
+}
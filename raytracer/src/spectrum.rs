@@ -0,0 +1,291 @@
+use crate::clamp;
+use crate::vec3::Vec3;
+use std::ops::{Add, AddAssign, Div, Mul};
+
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+pub const N_SPECTRUM_SAMPLES: usize = 4;
+
+//integral of cie_xyz(lambda).y over [LAMBDA_MIN, LAMBDA_MAX]
+const CIE_Y_INTEGRAL: f64 = 106.922;
+//linear sRGB of the equal energy spectrum, divided out so that a constant
+//spectrum of 1 lands on white
+const WHITE_BALANCE: [f64; 3] = [1.200268, 0.949699, 0.908296];
+
+fn piecewise_gaussian(x: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+    let t = (x - mu) / if x < mu { sigma1 } else { sigma2 };
+    (-0.5 * t * t).exp()
+}
+
+//CIE 1931 2-degree observer, multi-lobe fit from Wyman, Sloan and Shirley 2013
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3::new(
+        1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+            + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+            + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+            + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+//linear sRGB, D65 white
+pub fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    Vec3::new(
+        3.2404542 * xyz.x - 1.5371385 * xyz.y - 0.4985314 * xyz.z,
+        -0.9692660 * xyz.x + 1.8760108 * xyz.y + 0.0415560 * xyz.z,
+        0.0556434 * xyz.x - 0.2040259 * xyz.y + 1.0572252 * xyz.z,
+    )
+}
+
+//spectral radiance of a black body, lambda in nm, temperature in kelvin
+pub fn planck(lambda: f64, temperature: f64) -> f64 {
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    let c = 299792458.0;
+    let h = 6.62606957e-34;
+    let kb = 1.3806488e-23;
+    let l = lambda * 1e-9;
+    2.0 * h * c * c / (l.powi(5) * ((h * c / (l * kb * temperature)).exp() - 1.0))
+}
+
+//chromaticity of a black body as linear sRGB, normalized to unit luminance
+pub fn blackbody_color(temperature: f64) -> Vec3 {
+    let mut xyz = Vec3::zero();
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        xyz += cie_xyz(lambda) * planck(lambda, temperature);
+        lambda += 5.0;
+    }
+    if xyz.y <= 0.0 {
+        return Vec3::zero();
+    }
+    let rgb = xyz_to_rgb(xyz / xyz.y);
+    Vec3::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0))
+}
+
+//linear sRGB of a reflectance spectrum seen under an equal energy illuminant,
+//a constant spectrum maps to the matching grey
+pub fn reflectance_to_rgb<F: Fn(f64) -> f64>(f: F) -> Vec3 {
+    let mut xyz = Vec3::zero();
+    let mut white = Vec3::zero();
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        let cie = cie_xyz(lambda);
+        xyz += cie * f(lambda);
+        white += cie;
+        lambda += 10.0;
+    }
+    let rgb = xyz_to_rgb(xyz);
+    let w = xyz_to_rgb(white);
+    Vec3::new(
+        (rgb.x / w.x).max(0.0),
+        (rgb.y / w.y).max(0.0),
+        (rgb.z / w.z).max(0.0),
+    )
+}
+
+//Hero wavelength sampling: one uniform wavelength plus N - 1 equally spaced
+//rotations of it across the visible range.
+#[derive(Clone, Copy, Debug)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; N_SPECTRUM_SAMPLES],
+    pub pdf: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [0.0; N_SPECTRUM_SAMPLES];
+        lambda[0] = LAMBDA_MIN + u * range;
+        let delta = range / N_SPECTRUM_SAMPLES as f64;
+        for i in 1..N_SPECTRUM_SAMPLES {
+            lambda[i] = lambda[i - 1] + delta;
+            if lambda[i] > LAMBDA_MAX {
+                lambda[i] -= range;
+            }
+        }
+        Self {
+            lambda,
+            pdf: [1.0 / range; N_SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    //only the hero wavelength survives wavelength dependent scattering such as dispersion
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for p in self.pdf[1..].iter_mut() {
+            *p = 0.0;
+        }
+        self.pdf[0] /= N_SPECTRUM_SAMPLES as f64;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|p| *p == 0.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampledSpectrum {
+    pub c: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn new(v: f64) -> Self {
+        Self {
+            c: [v; N_SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self::new(0.0)
+    }
+
+    //smooth upsampling of a linear sRGB colour, exact for greys
+    pub fn from_rgb(rgb: Vec3, wavelengths: &SampledWavelengths) -> Self {
+        let mut c = [0.0; N_SPECTRUM_SAMPLES];
+        for (v, lambda) in c.iter_mut().zip(wavelengths.lambda.iter()) {
+            *v = rgb_to_spectrum(rgb, *lambda);
+        }
+        Self { c }
+    }
+
+    //monte carlo estimate of the colour seen by the film, in linear sRGB
+    pub fn to_rgb(self, wavelengths: &SampledWavelengths) -> Vec3 {
+        let mut xyz = Vec3::zero();
+        for i in 0..N_SPECTRUM_SAMPLES {
+            if wavelengths.pdf[i] > 0.0 {
+                xyz += cie_xyz(wavelengths.lambda[i]) * (self.c[i] / wavelengths.pdf[i]);
+            }
+        }
+        xyz /= N_SPECTRUM_SAMPLES as f64 * CIE_Y_INTEGRAL;
+        let rgb = xyz_to_rgb(xyz);
+        Vec3::new(
+            rgb.x / WHITE_BALANCE[0],
+            rgb.y / WHITE_BALANCE[1],
+            rgb.z / WHITE_BALANCE[2],
+        )
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        let mut c = self.c;
+        for (v, o) in c.iter_mut().zip(other.c.iter()) {
+            *v += *o;
+        }
+        Self { c }
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        let mut c = self.c;
+        for (v, o) in c.iter_mut().zip(other.c.iter()) {
+            *v *= *o;
+        }
+        Self { c }
+    }
+}
+
+impl Mul<f64> for SampledSpectrum {
+    type Output = Self;
+    fn mul(self, other: f64) -> Self {
+        let mut c = self.c;
+        for v in c.iter_mut() {
+            *v *= other;
+        }
+        Self { c }
+    }
+}
+
+impl Div<f64> for SampledSpectrum {
+    type Output = Self;
+    fn div(self, other: f64) -> Self {
+        self * (1.0 / other)
+    }
+}
+
+fn smoothstep(a: f64, b: f64, x: f64) -> f64 {
+    let t = clamp((x - a) / (b - a), 0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+//blue, green and red basis spectra forming a partition of unity, so reflectances
+//stay in [0, 1] and white upsamples to a constant spectrum
+pub fn rgb_to_spectrum(rgb: Vec3, lambda: f64) -> f64 {
+    let blue = 1.0 - smoothstep(480.0, 510.0, lambda);
+    let red = smoothstep(570.0, 600.0, lambda);
+    let green = 1.0 - blue - red;
+    rgb.x * red + rgb.y * green + rgb.z * blue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cie_peaks() {
+        assert!((cie_xyz(555.0).y - 1.0).abs() < 0.05);
+        assert!(cie_xyz(800.0).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_blackbody_color() {
+        let warm = blackbody_color(1500.0);
+        assert!(warm.x > warm.y && warm.y > warm.z);
+        let daylight = blackbody_color(6500.0);
+        assert!((daylight.x - daylight.z).abs() < 0.15);
+        assert!(blackbody_color(0.0).near_zero());
+    }
+
+    #[test]
+    fn test_rgb_round_trip() {
+        let colors = [
+            Vec3::ones(),
+            Vec3::new(0.5, 0.5, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for rgb in colors.iter() {
+            //deterministic stratified estimate over the hero offsets
+            let n = 2000;
+            let mut sum = Vec3::zero();
+            for i in 0..n {
+                let wavelengths = SampledWavelengths::sample_uniform((i as f64 + 0.5) / n as f64);
+                sum += SampledSpectrum::from_rgb(*rgb, &wavelengths).to_rgb(&wavelengths);
+            }
+            assert!((sum / n as f64 - *rgb).length() < 0.1);
+        }
+    }
+
+    #[test]
+    fn test_terminate_secondary_keeps_estimate() {
+        let grey = Vec3::new(0.5, 0.5, 0.5);
+        let n = 2000;
+        let mut sum = Vec3::zero();
+        for i in 0..n {
+            let mut wavelengths = SampledWavelengths::sample_uniform((i as f64 + 0.5) / n as f64);
+            wavelengths.terminate_secondary();
+            sum += SampledSpectrum::from_rgb(grey, &wavelengths).to_rgb(&wavelengths);
+        }
+        assert!((sum / n as f64 - grey).length() < 0.01);
+    }
+}
@@ -3,19 +3,20 @@
 use crate::aabb::AABB;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::onb::ONB;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
-use crate::{INF, ONB, PI};
-use std::sync::Arc;
+use crate::{INF, PI};
 
-pub struct Sphere {
+#[derive(Clone)]
+pub struct Sphere<T: Material> {
     pub center: Vec3,
     pub radius: f64,
-    pub mat_ptr: Arc<dyn Material>,
+    pub mat_ptr: T,
 }
 
-impl Sphere {
-    pub fn new(cen: Vec3, r: f64, m: Arc<dyn Material>) -> Self {
+impl<T: Material> Sphere<T> {
+    pub fn new(cen: Vec3, r: f64, m: T) -> Self {
         Self {
             center: Vec3 {
                 x: cen.x,
@@ -32,17 +33,25 @@ impl Sphere {
         let phi = (-p.z).atan2(p.x) + PI;
         (phi / (2.0 * PI), theta / PI)
     }
+
+    fn get_sphere_tangents(n: &Vec3, radius: f64) -> (Vec3, Vec3) {
+        let sin_theta = (1.0 - n.y * n.y).sqrt().max(1e-8);
+        (
+            Vec3::new(n.z, 0.0, -n.x) * (2.0 * PI * radius),
+            Vec3::new(-n.x * n.y / sin_theta, sin_theta, -n.z * n.y / sin_theta) * (PI * radius),
+        )
+    }
 }
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl<T: 'static + Clone + Material + Send + Sync> Hittable for Sphere<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = r.orig - self.center;
         let a = r.dir.squared_length();
         let half_b = oc * r.dir;
         let c = oc.squared_length() - self.radius * self.radius;
         let discriminant = half_b * half_b - a * c;
         if discriminant < 0.0 {
-            return None;
+            None
         } else {
             let sqrtd = discriminant.sqrt();
             let mut root = (-half_b - sqrtd) / a;
@@ -52,7 +61,7 @@ impl Hittable for Sphere {
                     return None;
                 }
             }
-            let mut rec = HitRecord::new();
+            let mut rec = HitRecord::new(&self.mat_ptr);
             rec.t = root;
             rec.p = r.at(root);
             let outward_normal = (rec.p - self.center) / self.radius;
@@ -60,7 +69,9 @@ impl Hittable for Sphere {
             let tuple = Self::get_sphere_uv(&outward_normal);
             rec.u = tuple.0;
             rec.v = tuple.1;
-            rec.mat_ptr = self.mat_ptr.clone();
+            let (dpdu, dpdv) = Self::get_sphere_tangents(&outward_normal, self.radius);
+            rec.dpdu = dpdu;
+            rec.dpdv = dpdv;
             Some(rec)
         }
     }
@@ -92,26 +103,29 @@ impl Hittable for Sphere {
         uvw.build_from_w(direction);
         uvw.local_with_vec(Vec3::random_to_sphere(self.radius, distance_squared))
     }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_surface(&self) -> (Vec3, Vec3) {
+        let n = Vec3::random_unit_vector();
+        (self.center + n * self.radius, n)
+    }
 }
 
-pub struct MovingSphere {
+#[derive(Clone)]
+pub struct MovingSphere<T: Material> {
     pub center0: Vec3,
     pub center1: Vec3,
     pub time0: f64,
     pub time1: f64,
     pub radius: f64,
-    pub mat_ptr: Arc<dyn Material>,
+    pub mat_ptr: T,
 }
 
-impl MovingSphere {
-    pub fn new(
-        cen0: Vec3,
-        cen1: Vec3,
-        _time0: f64,
-        _time1: f64,
-        r: f64,
-        m: Arc<dyn Material>,
-    ) -> Self {
+impl<T: Material> MovingSphere<T> {
+    pub fn new(cen0: Vec3, cen1: Vec3, _time0: f64, _time1: f64, r: f64, m: T) -> Self {
         Self {
             center0: cen0,
             center1: cen1,
@@ -128,15 +142,15 @@ impl MovingSphere {
     }
 }
 
-impl Hittable for MovingSphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl<T: 'static + Clone + Material + Send + Sync> Hittable for MovingSphere<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = r.orig - self.center(r.tm);
         let a = r.dir.squared_length();
         let half_b = oc * r.dir;
         let c = oc.squared_length() - self.radius * self.radius;
         let discriminant = half_b * half_b - a * c;
         if discriminant < 0.0 {
-            return None;
+            None
         } else {
             let sqrtd = discriminant.sqrt();
             let mut root = (-half_b - sqrtd) / a;
@@ -146,12 +160,11 @@ impl Hittable for MovingSphere {
                     return None;
                 }
             }
-            let mut rec = HitRecord::new();
+            let mut rec = HitRecord::new(&self.mat_ptr);
             rec.t = root;
             rec.p = r.at(root);
             let outward_normal = (rec.p - self.center(r.tm)) / self.radius;
             rec.set_face_normal(r, &outward_normal);
-            rec.mat_ptr = self.mat_ptr.clone();
             Some(rec)
         }
     }
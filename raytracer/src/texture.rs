@@ -1,124 +1,543 @@
-use crate::clamp;
-use crate::perlin::Perlin;
-pub use crate::vec3::Vec3;
-use image::DynamicImage;
-use imageproc::drawing::Canvas;
-use std::path::Path;
-use std::sync::Arc;
-
-pub trait Texture {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
-}
-
-pub struct SolidColor {
-    color_value: Vec3,
-}
-
-impl SolidColor {
-    pub fn new_with_vec(c: Vec3) -> Self {
-        Self { color_value: c }
-    }
-
-    pub fn new_with_col(red: f64, green: f64, blue: f64) -> Self {
-        Self {
-            color_value: Vec3::new(red, green, blue),
-        }
-    }
-}
-
-impl Texture for SolidColor {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
-        self.color_value
-    }
-}
-
-pub struct CheckerTexture {
-    pub odd: Arc<dyn Texture>,
-    pub even: Arc<dyn Texture>,
-}
-
-impl CheckerTexture {
-    pub fn new_with_arc(_even: Arc<dyn Texture>, _odd: Arc<dyn Texture>) -> Self {
-        Self {
-            even: _even,
-            odd: _odd,
-        }
-    }
-
-    pub fn new_with_col(c1: Vec3, c2: Vec3) -> Self {
-        Self {
-            even: Arc::new(SolidColor::new_with_vec(c1)),
-            odd: Arc::new(SolidColor::new_with_vec(c2)),
-        }
-    }
-}
-
-impl Texture for CheckerTexture {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
-        let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
-        if sines < 0.0 {
-            self.odd.value(u, v, p)
-        } else {
-            self.even.value(u, v, p)
-        }
-    }
-}
-
-pub struct NoiseTexture {
-    noise: Perlin,
-    scale: f64,
-}
-
-impl NoiseTexture {
-    pub fn new(sc: f64) -> Self {
-        Self {
-            noise: Perlin::new(),
-            scale: sc,
-        }
-    }
-}
-
-impl Texture for NoiseTexture {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
-        Vec3::ones() * 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin())
-    }
-}
-
-pub struct ImageTexture {
-    image: DynamicImage,
-    width: u32,
-    height: u32,
-}
-
-impl ImageTexture {
-    pub fn new(filename: &str) -> Self {
-        let img = image::open(Path::new(filename)).unwrap();
-        Self {
-            image: img.clone(),
-            width: img.dimensions().0,
-            height: img.dimensions().1,
-        }
-    }
-}
-
-impl Texture for ImageTexture {
-    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
-        let _u = clamp(u, 0.0, 1.0);
-        let _v = 1.0 - clamp(v, 0.0, 1.0);
-        let mut i = (_u * self.width as f64) as u32;
-        let mut j = (_v * self.height as f64) as u32;
-        if i >= self.width {
-            i = self.width - 1;
-        }
-        if j >= self.height {
-            j = self.height - 1;
-        }
-        let color_scale = 1.0 / 255.0;
-        let pixel = self.image.get_pixel(i, j);
-        Vec3::new(
-            color_scale * pixel[0] as f64,
-            color_scale * pixel[1] as f64,
-            color_scale * pixel[2] as f64,
-        )
-    }
-}
+use crate::error::{RenderError, Result};
+use crate::perlin::Perlin;
+pub use crate::vec3::Vec3;
+use crate::{clamp, degrees_to_radians};
+use image::codecs::hdr::HdrDecoder;
+use image::ColorType;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
+
+    //lookup averaged over a footprint of the given width in uv units
+    fn filtered_value(&self, u: f64, v: f64, p: &Vec3, footprint: f64) -> Vec3 {
+        self.value(u, v, p)
+    }
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color_value: Vec3,
+}
+
+impl SolidColor {
+    pub fn new_with_vec(c: Vec3) -> Self {
+        Self { color_value: c }
+    }
+
+    pub fn new_with_col(red: f64, green: f64, blue: f64) -> Self {
+        Self {
+            color_value: Vec3::new(red, green, blue),
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.color_value
+    }
+}
+
+#[derive(Clone)]
+pub struct CheckerTexture<O: Texture, E: Texture> {
+    pub odd: O,
+    pub even: E,
+}
+
+impl<O: Texture, E: Texture> CheckerTexture<O, E> {
+    pub fn new(_even: E, _odd: O) -> Self {
+        Self {
+            even: _even,
+            odd: _odd,
+        }
+    }
+}
+
+impl<O: Texture, E: Texture> Texture for CheckerTexture<O, E> {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.filtered_value(u, v, p, 0.0)
+    }
+
+    fn filtered_value(&self, u: f64, v: f64, p: &Vec3, footprint: f64) -> Vec3 {
+        let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
+        if sines < 0.0 {
+            self.odd.filtered_value(u, v, p, footprint)
+        } else {
+            self.even.filtered_value(u, v, p, footprint)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(sc: f64) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale: sc,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::ones() * 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+    //bilinear on the two mip levels closest to the footprint
+    Trilinear,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl WrapMode {
+    fn apply(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+            WrapMode::Clamp => i.max(0).min(n - 1),
+        };
+        wrapped as usize
+    }
+}
+
+//rotation (degrees, around the texture center), then scale, then offset
+#[derive(Clone, Copy)]
+pub struct UvTransform {
+    pub scale_u: f64,
+    pub scale_v: f64,
+    pub offset_u: f64,
+    pub offset_v: f64,
+    pub rotation: f64,
+}
+
+impl UvTransform {
+    pub fn new(scale_u: f64, scale_v: f64, offset_u: f64, offset_v: f64, rotation: f64) -> Self {
+        Self {
+            scale_u,
+            scale_v,
+            offset_u,
+            offset_v,
+            rotation,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 1.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn apply(&self, u: f64, v: f64) -> (f64, f64) {
+        let (sin, cos) = degrees_to_radians(self.rotation).sin_cos();
+        let (du, dv) = (u - 0.5, v - 0.5);
+        let ru = cos * du - sin * dv + 0.5;
+        let rv = sin * du + cos * dv + 0.5;
+        (
+            ru * self.scale_u + self.offset_u,
+            rv * self.scale_v + self.offset_v,
+        )
+    }
+
+    //how much the transform stretches a footprint
+    pub fn footprint_scale(&self) -> f64 {
+        self.scale_u.abs().max(self.scale_v.abs())
+    }
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<[f32; 3]>,
+}
+
+impl MipLevel {
+    fn texel(&self, i: usize, j: usize) -> Vec3 {
+        let t = self.texels[j * self.width + i];
+        Vec3::new(t[0] as f64, t[1] as f64, t[2] as f64)
+    }
+
+    //box filtered half resolution level, odd edges fold into the last texel
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                let mut sum = [0.0_f32; 3];
+                for (di, dj) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                    let x = (2 * i + di).min(self.width - 1);
+                    let y = (2 * j + dj).min(self.height - 1);
+                    let t = self.texels[y * self.width + x];
+                    for c in 0..3 {
+                        sum[c] += 0.25 * t[c];
+                    }
+                }
+                texels.push(sum);
+            }
+        }
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+//How stored texel values map to shading values. Colour maps are usually sRGB
+//encoded; Data is for normal, roughness and other maps that are used as stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+    Data,
+}
+
+impl ColorSpace {
+    pub fn decode(self, c: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb => srgb_to_linear(c),
+            ColorSpace::Linear | ColorSpace::Data => c,
+        }
+    }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+//inverse of srgb_to_linear, 8 bit output images are encoded with it
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+//decoded base level and whether the file holds float data; with no colour
+//space given, integer images are decoded as sRGB and float images kept linear
+fn load_base_level(filename: &str, space: Option<ColorSpace>) -> Result<(MipLevel, bool)> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let (width, height, mut texels, is_float): (u32, u32, Vec<[f32; 3]>, bool) =
+        match extension.as_deref() {
+            Some("hdr") => {
+                let file = File::open(filename).map_err(|e| RenderError::io(filename, e))?;
+                let decoder = HdrDecoder::new(BufReader::new(file))
+                    .map_err(|e| RenderError::image(filename, e))?;
+                let meta = decoder.metadata();
+                let texels = decoder
+                    .read_image_hdr()
+                    .map_err(|e| RenderError::image(filename, e))?
+                    .iter()
+                    .map(|p| p.0)
+                    .collect();
+                (meta.width, meta.height, texels, true)
+            }
+            #[cfg(not(feature = "exr"))]
+            Some("exr") => return Err(RenderError::exr_disabled(filename)),
+            #[cfg(feature = "exr")]
+            Some("exr") => {
+                let image = exr::prelude::read_first_rgba_layer_from_file(
+                    filename,
+                    |resolution, _| {
+                        (
+                            resolution.width(),
+                            vec![[0.0_f32; 3]; resolution.width() * resolution.height()],
+                        )
+                    },
+                    |(width, texels): &mut (usize, Vec<[f32; 3]>),
+                     position,
+                     (r, g, b, _): (f32, f32, f32, f32)| {
+                        texels[position.y() * *width + position.x()] = [r, g, b];
+                    },
+                )
+                .map_err(|e| RenderError::exr(filename, e))?;
+                let size = image.layer_data.size;
+                let (_, texels) = image.layer_data.channel_data.pixels;
+                (size.width() as u32, size.height() as u32, texels, true)
+            }
+            _ => {
+                let img = image::open(Path::new(filename))
+                    .map_err(|e| RenderError::image(filename, e))?;
+                let space = space.unwrap_or(ColorSpace::Srgb);
+                match img.color() {
+                    ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+                        let img = img.to_rgb16();
+                        let scale = 1.0 / 65535.0;
+                        let texels = img
+                            .pixels()
+                            .map(|p| {
+                                [
+                                    space.decode(scale * p[0] as f32),
+                                    space.decode(scale * p[1] as f32),
+                                    space.decode(scale * p[2] as f32),
+                                ]
+                            })
+                            .collect();
+                        (img.width(), img.height(), texels, false)
+                    }
+                    _ => {
+                        let img = img.to_rgb8();
+                        let mut table = [0.0_f32; 256];
+                        for (i, t) in table.iter_mut().enumerate() {
+                            *t = space.decode(i as f32 / 255.0);
+                        }
+                        let texels = img
+                            .pixels()
+                            .map(|p| {
+                                [
+                                    table[p[0] as usize],
+                                    table[p[1] as usize],
+                                    table[p[2] as usize],
+                                ]
+                            })
+                            .collect();
+                        (img.width(), img.height(), texels, false)
+                    }
+                }
+            }
+        };
+    if width == 0 || height == 0 {
+        return Err(RenderError::invalid_data(filename, "image has no pixels"));
+    }
+    if is_float {
+        if let Some(space) = space {
+            for t in texels.iter_mut() {
+                for c in t.iter_mut() {
+                    *c = space.decode(*c);
+                }
+            }
+        }
+    }
+    let base = MipLevel {
+        width: width as usize,
+        height: height as usize,
+        texels,
+    };
+    Ok((base, is_float))
+}
+
+//Image texture with a mip pyramid built at load. Lookups are trilinear by
+//default and use the hit footprint to pick the level; v = 0 is the bottom row.
+#[derive(Clone)]
+pub struct ImageTexture {
+    levels: Arc<Vec<MipLevel>>,
+    pub space: ColorSpace,
+    pub filter: TextureFilter,
+    pub wrap: WrapMode,
+    pub transform: UvTransform,
+}
+
+impl ImageTexture {
+    //8 and 16 bit images are taken as sRGB, float images (.hdr, .exr) as linear
+    pub fn new(filename: &str) -> Result<Self> {
+        let (base, is_float) = load_base_level(filename, None)?;
+        let space = if is_float {
+            ColorSpace::Linear
+        } else {
+            ColorSpace::Srgb
+        };
+        Ok(Self::new_with_level(base, space))
+    }
+
+    pub fn new_with_color_space(filename: &str, space: ColorSpace) -> Result<Self> {
+        let (base, _) = load_base_level(filename, Some(space))?;
+        Ok(Self::new_with_level(base, space))
+    }
+
+    //flat magenta, hard to miss in a render, for callers that would rather
+    //render on without a texture that failed to load
+    pub fn placeholder() -> Self {
+        let base = MipLevel {
+            width: 1,
+            height: 1,
+            texels: vec![[1.0, 0.0, 1.0]],
+        };
+        Self::new_with_level(base, ColorSpace::Linear)
+    }
+
+    //texels of the base level must already be decoded
+    fn new_with_level(base: MipLevel, space: ColorSpace) -> Self {
+        let mut levels = vec![base];
+        while levels.last().unwrap().width > 1 || levels.last().unwrap().height > 1 {
+            let next = levels.last().unwrap().downsample();
+            levels.push(next);
+        }
+        Self {
+            levels: Arc::new(levels),
+            space,
+            filter: TextureFilter::Trilinear,
+            wrap: WrapMode::Clamp,
+            transform: UvTransform::identity(),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: UvTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    fn nearest(&self, level: &MipLevel, x: f64, y: f64) -> Vec3 {
+        let i = self.wrap.apply(x.floor() as i64, level.width);
+        let j = self.wrap.apply(y.floor() as i64, level.height);
+        level.texel(i, j)
+    }
+
+    //x and y in texels, texel centers at half integers
+    fn bilinear(&self, level: &MipLevel, x: f64, y: f64) -> Vec3 {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let i0 = self.wrap.apply(x0 as i64, level.width);
+        let i1 = self.wrap.apply(x0 as i64 + 1, level.width);
+        let j0 = self.wrap.apply(y0 as i64, level.height);
+        let j1 = self.wrap.apply(y0 as i64 + 1, level.height);
+        (level.texel(i0, j0) * (1.0 - fx) + level.texel(i1, j0) * fx) * (1.0 - fy)
+            + (level.texel(i0, j1) * (1.0 - fx) + level.texel(i1, j1) * fx) * fy
+    }
+
+    fn lookup(&self, level: usize, u: f64, v: f64) -> Vec3 {
+        let level = &self.levels[level];
+        let x = u * level.width as f64;
+        let y = (1.0 - v) * level.height as f64;
+        match self.filter {
+            TextureFilter::Nearest => self.nearest(level, x, y),
+            _ => self.bilinear(level, x, y),
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.filtered_value(u, v, p, 0.0)
+    }
+
+    fn filtered_value(&self, u: f64, v: f64, p: &Vec3, footprint: f64) -> Vec3 {
+        let (u, v) = self.transform.apply(u, v);
+        if self.filter != TextureFilter::Trilinear {
+            return self.lookup(0, u, v);
+        }
+        let base = &self.levels[0];
+        let texels =
+            footprint * self.transform.footprint_scale() * base.width.max(base.height) as f64;
+        let lod = clamp(texels.max(1e-8).log2(), 0.0, (self.levels.len() - 1) as f64);
+        let l0 = (lod.floor() as usize).min(self.levels.len() - 1);
+        let l1 = (l0 + 1).min(self.levels.len() - 1);
+        let f = lod - l0 as f64;
+        if f <= 0.0 || l0 == l1 {
+            return self.lookup(l0, u, v);
+        }
+        self.lookup(l0, u, v) * (1.0 - f) + self.lookup(l1, u, v) * f
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_path;
+    use std::fs;
+
+    #[test]
+    fn test_wrap_modes() {
+        let wrapped: Vec<usize> = (-3..7).map(|i| WrapMode::Repeat.apply(i, 4)).collect();
+        assert_eq!(wrapped, vec![1, 2, 3, 0, 1, 2, 3, 0, 1, 2]);
+        let mirrored: Vec<usize> = (-3..7).map(|i| WrapMode::Mirror.apply(i, 4)).collect();
+        assert_eq!(mirrored, vec![2, 1, 0, 0, 1, 2, 3, 3, 2, 1]);
+        let clamped: Vec<usize> = (-3..7).map(|i| WrapMode::Clamp.apply(i, 4)).collect();
+        assert_eq!(clamped, vec![0, 0, 0, 0, 1, 2, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_uv_transform() {
+        let (u, v) = UvTransform::identity().apply(0.25, 0.75);
+        assert!((u - 0.25).abs() < 1e-12 && (v - 0.75).abs() < 1e-12);
+        //a quarter turn about the center, then tiled twice
+        let (u, v) = UvTransform::new(2.0, 2.0, 0.5, 0.0, 90.0).apply(1.0, 0.5);
+        assert!((u - 1.5).abs() < 1e-12 && (v - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_srgb_decode() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_load_color_spaces() {
+        let path = temp_path("texture_16bit.png");
+        let img = image::ImageBuffer::from_pixel(2, 2, image::Rgb([32768_u16, 0, 65535]));
+        image::DynamicImage::ImageRgb16(img).save(&path).unwrap();
+        let png = path.to_str().unwrap();
+        let p = Vec3::zero();
+        let srgb = ImageTexture::new(png).unwrap().value(0.5, 0.5, &p);
+        let data = ImageTexture::new_with_color_space(png, ColorSpace::Data)
+            .unwrap()
+            .value(0.5, 0.5, &p);
+        fs::remove_file(&path).unwrap();
+        assert!((srgb.x - 0.214).abs() < 1e-3 && srgb.y == 0.0 && (srgb.z - 1.0).abs() < 1e-6);
+        assert!((data.x - 0.5).abs() < 1e-4);
+    }
+
+    //float images keep values above one
+    #[cfg(feature = "exr")]
+    #[test]
+    fn test_load_float_exr() {
+        let p = Vec3::zero();
+        let path = temp_path("texture_float.exr");
+        exr::prelude::write_rgb_file(&path, 2, 2, |_, _| (4.0_f32, 0.5_f32, 0.0_f32)).unwrap();
+        let hdr = ImageTexture::new(path.to_str().unwrap())
+            .unwrap()
+            .value(0.5, 0.5, &p);
+        fs::remove_file(&path).unwrap();
+        assert!((hdr - Vec3::new(4.0, 0.5, 0.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_missing_image() {
+        assert!(ImageTexture::new("jpg/no_such_texture.jpg").is_err());
+        let c = ImageTexture::placeholder().value(0.3, 0.7, &Vec3::zero());
+        assert_eq!(c, Vec3::new(1.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::hittable::HitRecord;
+use crate::material::{Dielectric, Material, Metal, ScatterRecord};
+use crate::pdf::CosinePDF;
+use crate::ray::Ray;
+use crate::spectrum::{reflectance_to_rgb, rgb_to_spectrum};
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use crate::{clamp, random_0_1, PI};
+use std::sync::Arc;
+
+//Airy reflectance of a film of index n2 and thickness d (nm) between n1 and n3,
+//averaged over s and p polarization. Amplitudes are kept real, so a substrate
+//only contributes through its index.
+pub fn airy_reflectance(cos_i: f64, n1: f64, n2: f64, n3: f64, d: f64, lambda: f64) -> f64 {
+    let sin2_i = 1.0 - cos_i * cos_i;
+    let sin2_film = (n1 / n2) * (n1 / n2) * sin2_i;
+    let sin2_t = (n1 / n3) * (n1 / n3) * sin2_i;
+    if sin2_film >= 1.0 || sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_film = (1.0 - sin2_film).sqrt();
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let cos_delta = (4.0 * PI * n2 * d * cos_film / lambda).cos();
+    let airy = |r12: f64, r23: f64| {
+        let cross = 2.0 * r12 * r23 * cos_delta;
+        (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+    };
+    let r12s = (n1 * cos_i - n2 * cos_film) / (n1 * cos_i + n2 * cos_film);
+    let r23s = (n2 * cos_film - n3 * cos_t) / (n2 * cos_film + n3 * cos_t);
+    let r12p = (n2 * cos_i - n1 * cos_film) / (n2 * cos_i + n1 * cos_film);
+    let r23p = (n3 * cos_film - n2 * cos_t) / (n3 * cos_film + n2 * cos_t);
+    clamp(0.5 * (airy(r12s, r23s) + airy(r12p, r23p)), 0.0, 1.0)
+}
+
+//film thickness in nm is d_min + (d_max - d_min) * the texture value
+#[derive(Clone)]
+pub struct ThinFilm<T: Texture> {
+    pub thickness: T,
+    pub d_min: f64,
+    pub d_max: f64,
+    pub ior: f64,
+}
+
+impl<T: Texture> ThinFilm<T> {
+    pub fn new(thickness: T, d_min: f64, d_max: f64, ior: f64) -> Self {
+        Self {
+            thickness,
+            d_min,
+            d_max,
+            ior,
+        }
+    }
+
+    pub fn thickness_at(&self, rec: &HitRecord) -> f64 {
+        let t = self.thickness.value(rec.u, rec.v, &rec.p);
+        self.d_min + (self.d_max - self.d_min) * clamp((t.x + t.y + t.z) / 3.0, 0.0, 1.0)
+    }
+
+    //rays without a wavelength get the reflectance integrated to RGB
+    pub fn reflectance<F: Fn(f64) -> f64>(
+        &self,
+        cos_i: f64,
+        n1: f64,
+        n3: F,
+        d: f64,
+        lambda: f64,
+    ) -> Vec3 {
+        if lambda > 0.0 {
+            Vec3::ones() * airy_reflectance(cos_i, n1, self.ior, n3(lambda), d, lambda)
+        } else {
+            reflectance_to_rgb(|l| airy_reflectance(cos_i, n1, self.ior, n3(l), d, l))
+        }
+    }
+}
+
+//dielectric coated on its outside by a thin film, e.g. soap bubbles (ir = 1) or oil on water
+#[derive(Clone)]
+pub struct FilmDielectric<T: Texture> {
+    pub base: Dielectric,
+    pub film: ThinFilm<T>,
+}
+
+impl<T: Texture> FilmDielectric<T> {
+    pub fn new(base: Dielectric, film: ThinFilm<T>) -> Self {
+        Self { base, film }
+    }
+}
+
+impl<T: Texture> Material for FilmDielectric<T> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let ir = self.base.ior(r.lambda);
+        let (n_i, n_t) = if rec.front_face { (1.0, ir) } else { (ir, 1.0) };
+        let unit_direction = r.dir.unit();
+        let cos_theta = clamp(-unit_direction * rec.normal, 0.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let reflectance = self.film.reflectance(
+            cos_theta,
+            n_i,
+            |_| n_t,
+            self.film.thickness_at(rec),
+            r.lambda,
+        );
+        let p_reflect = (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+        let (direction, attenuation, eta) = if n_i / n_t * sin_theta > 1.0 {
+            (Vec3::reflect(unit_direction, rec.normal), Vec3::ones(), 1.0)
+        } else if random_0_1() < p_reflect {
+            (
+                Vec3::reflect(unit_direction, rec.normal),
+                reflectance / p_reflect,
+                1.0,
+            )
+        } else {
+            (
+                Vec3::refract(unit_direction, rec.normal, n_i / n_t),
+                (Vec3::ones() - reflectance) / (1.0 - p_reflect),
+                n_i / n_t,
+            )
+        };
+        let s_rec = ScatterRecord {
+            attenuation,
+            specular_ray: Ray::new(rec.p, direction, r.tm),
+            pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
+            is_specular: true,
+            eta,
+        };
+        Some(s_rec)
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        true
+    }
+}
+
+//metal under a thin film, the metal enters through the index matching its albedo at normal incidence
+#[derive(Clone)]
+pub struct FilmMetal<T: Texture> {
+    pub base: Metal,
+    pub film: ThinFilm<T>,
+}
+
+impl<T: Texture> FilmMetal<T> {
+    pub fn new(base: Metal, film: ThinFilm<T>) -> Self {
+        Self { base, film }
+    }
+
+    fn substrate_ior(&self, lambda: f64) -> f64 {
+        let f0 = clamp(rgb_to_spectrum(self.base.albedo, lambda), 0.0, 0.99).sqrt();
+        (1.0 + f0) / (1.0 - f0)
+    }
+}
+
+impl<T: Texture> Material for FilmMetal<T> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let unit_direction = r.dir.unit();
+        let cos_theta = clamp(-unit_direction * rec.normal, 0.0, 1.0);
+        let reflected = Vec3::reflect(unit_direction, rec.normal);
+        let s_rec = ScatterRecord {
+            specular_ray: Ray::new(
+                rec.p,
+                reflected + Vec3::random_in_unit_sphere() * self.base.fuzz,
+                0.0,
+            ),
+            attenuation: self.film.reflectance(
+                cos_theta,
+                1.0,
+                |l| self.substrate_ior(l),
+                self.film.thickness_at(rec),
+                r.lambda,
+            ),
+            is_specular: true,
+            pdf_ptr: Arc::new(CosinePDF::new(&Vec3::ones())),
+            eta: 1.0,
+        };
+        Some(s_rec)
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_thickness_is_fresnel() {
+        let r0 = (1.5 - 1.0) / (1.5 + 1.0);
+        let r = airy_reflectance(1.0, 1.0, 1.33, 1.5, 0.0, 550.0);
+        assert!((r - r0 * r0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_quarter_wave_coating() {
+        let n2 = 1.5_f64.sqrt();
+        let d = 550.0 / (4.0 * n2);
+        assert!(airy_reflectance(1.0, 1.0, n2, 1.5, d, 550.0) < 1e-9);
+        //half wave films are absent
+        let r = airy_reflectance(1.0, 1.0, n2, 1.5, 2.0 * d, 550.0);
+        assert!((r - 0.04).abs() < 1e-9);
+    }
+}
@@ -45,16 +45,26 @@ impl Vec3 {
         }
     }
 
+    //a zero vector stays zero, use try_unit where that case needs handling
     pub fn unit(&self) -> Self {
+        self.try_unit().unwrap_or_else(Self::zero)
+    }
+
+    pub fn try_unit(&self) -> Option<Self> {
         let len = self.length();
-        if len == 0.0 {
-            panic!("DIVIDED BY ZERO");
+        if len == 0.0 || !len.is_finite() {
+            return None;
         }
-        Self {
+        Some(Self {
             x: self.x / len,
             y: self.y / len,
             z: self.z / len,
-        }
+        })
+    }
+
+    //Rec. 709 luminance of a linear RGB colour
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
     }
 
     pub fn near_zero(&self) -> bool {
@@ -435,8 +445,12 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn test_unit_panic() {
-        Vec3::new(0.0, 0.0, 0.0).unit();
+    fn test_unit_zero() {
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0).unit(), Vec3::zero());
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0).try_unit(), None);
+        assert_eq!(
+            Vec3::new(0.0, 3.0, 0.0).try_unit(),
+            Some(Vec3::new(0.0, 1.0, 0.0))
+        );
     }
 }